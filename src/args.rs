@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
 use std::path::PathBuf;
 
 use crate::chunk_type::ChunkType;
@@ -9,29 +10,312 @@ pub enum PngMeArgs {
     Decode(DecodeArgs),
     Remove(RemoveArgs),
     Print(PrintArgs),
+    Modify(ModifyArgs),
+    FixCrc(FixCrcArgs),
+    Count(CountArgs),
+    Trailer(TrailerArgs),
+    Validate(ValidateArgs),
+    Verify(VerifyArgs),
+    BatchEncode(BatchEncodeArgs),
+    Extract(ExtractArgs),
+    Graft(GraftArgs),
+    Diff(DiffArgs),
+    Scan(ScanArgs),
+    Info(InfoArgs),
+    Text(TextArgs),
+    SetText(SetTextArgs),
+    #[clap(hide = true)]
+    Completions(CompletionsArgs),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// When to color the output.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Only when writing to a terminal and `NO_COLOR` isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+/// Kinds of chunks `--only` can restrict output to.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChunkFilter {
+    /// Chunks with a private (lowercase second letter) type
+    Private,
+    /// Chunks with an ancillary (lowercase first letter) type
+    Ancillary,
+}
+
+impl ChunkFilter {
+    pub fn matches(&self, chunk_type: &ChunkType) -> bool {
+        match self {
+            ChunkFilter::Private => !chunk_type.is_public(),
+            ChunkFilter::Ancillary => !chunk_type.is_critical(),
+        }
+    }
 }
 
 #[derive(Parser)]
 pub struct EncodeArgs {
     pub file_path: PathBuf,
-    pub chunk_type: ChunkType,
-    pub message: String,
+    #[clap(required_unless_present = "random_type")]
+    pub chunk_type: Option<ChunkType>,
+    #[clap(required_unless_present = "message_file")]
+    pub message: Option<String>,
+    /// Where to write the result. Without it, the input is never
+    /// overwritten unless --in-place is given: the result goes to
+    /// `<stem>.pngme.png` next to it, or into --output-dir
     pub output_file: Option<PathBuf>,
+    /// Read the message as raw bytes from a file, or `-` for stdin
+    #[clap(long, value_name = "PATH", conflicts_with = "message")]
+    pub message_file: Option<PathBuf>,
+    /// Write the resulting PNG to stdout instead of a file
+    #[clap(long, conflicts_with = "output_file")]
+    pub stdout: bool,
+    /// Overwrite the input file with the result
+    #[clap(long, conflicts_with_all = ["output_file", "stdout", "output_dir"])]
+    pub in_place: bool,
+    /// Write the result into this directory under the input's file name
+    #[clap(long, value_name = "DIR", conflicts_with_all = ["output_file", "stdout"])]
+    pub output_dir: Option<PathBuf>,
+    /// Encrypt the message with a key derived from this password
+    #[clap(long)]
+    pub password: Option<String>,
+    /// Deflate the message before storing it
+    #[clap(long)]
+    pub compress: bool,
+    /// The message is base64 and is decoded to raw bytes before storing it
+    #[clap(long)]
+    pub base64: bool,
+    /// Refuse to store more than this many bytes of chunk data
+    #[clap(long, value_name = "BYTES")]
+    pub max_message_len: Option<usize>,
+    /// Insert the chunk right after the first chunk of this type instead of
+    /// before IEND
+    #[clap(long, value_name = "TYPE")]
+    pub after: Option<ChunkType>,
+    /// Copy the file to `<file>.bak` before overwriting it; fails if that
+    /// backup already exists
+    #[clap(long)]
+    pub backup: bool,
+    /// Re-read the written file and check that it holds the message
+    #[clap(long, conflicts_with = "stdout")]
+    pub verify: bool,
+    /// Store the message under a generated private, ancillary,
+    /// safe-to-copy chunk type, which is always printed. Takes the place of
+    /// the chunk type argument, so the message comes from --message-file
+    #[clap(long, conflicts_with = "chunk_type", requires = "message_file")]
+    pub random_type: bool,
 }
 
 #[derive(Parser)]
 pub struct DecodeArgs {
     pub file_path: PathBuf,
-    pub chunk_type: ChunkType,
+    #[clap(required_unless_present = "index")]
+    pub chunk_type: Option<ChunkType>,
+    /// Decode the Nth (zero-based) chunk of the file, whatever its type
+    #[clap(long, value_name = "N", conflicts_with_all = ["chunk_type", "all", "ignore_case"])]
+    pub index: Option<usize>,
+    /// Print every chunk of the given type instead of only the first
+    #[clap(long)]
+    pub all: bool,
+    /// Match the chunk type ignoring ASCII case
+    #[clap(long)]
+    pub ignore_case: bool,
+    /// Warn about chunks with a wrong CRC instead of failing
+    #[clap(long)]
+    pub ignore_crc: bool,
+    /// Read chunks with an invalid type, e.g. a lowercase reserved bit,
+    /// instead of failing
+    #[clap(long)]
+    pub lenient_type: bool,
+    /// Decrypt a message that was encoded with --password
+    #[clap(long)]
+    pub password: Option<String>,
+    /// Dump the data as hex and ASCII instead of printing it as text
+    #[clap(long, visible_alias = "pretty")]
+    pub hex: bool,
+    /// Print the data base64-encoded instead of as text
+    #[clap(long, conflicts_with = "hex")]
+    pub base64: bool,
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
 }
 
 #[derive(Parser)]
 pub struct RemoveArgs {
     pub file_path: PathBuf,
     pub chunk_type: ChunkType,
+    /// Remove the Nth (zero-based) chunk of the type instead of the first
+    #[clap(long, value_name = "N")]
+    pub index: Option<usize>,
+    /// Remove every chunk of the type
+    #[clap(long, conflicts_with_all = ["index", "print"])]
+    pub all: bool,
+    /// Print the removed chunk
+    #[clap(long)]
+    pub print: bool,
+    /// Allow removing critical chunks, which leaves the PNG unreadable
+    #[clap(long)]
+    pub force: bool,
+    /// Write the resulting PNG to stdout instead of back to the file
+    #[clap(long)]
+    pub stdout: bool,
+    /// Only report which chunks would be removed, without writing anything
+    #[clap(long, conflicts_with_all = ["print", "stdout"])]
+    pub dry_run: bool,
+    /// Copy the file to `<file>.bak` before overwriting it; fails if that
+    /// backup already exists
+    #[clap(long, conflicts_with = "stdout")]
+    pub backup: bool,
 }
 
 #[derive(Parser)]
 pub struct PrintArgs {
     pub file_path: PathBuf,
+    /// Show offset, length, CRC and property flags of each chunk
+    #[clap(long, conflicts_with = "summary")]
+    pub detailed: bool,
+    /// Show the byte offset of each chunk in the file
+    #[clap(long, conflicts_with_all = ["detailed", "summary"])]
+    pub offsets: bool,
+    /// With --detailed, dump each chunk's data as hex and ASCII
+    #[clap(long, requires = "detailed")]
+    pub pretty: bool,
+    /// Show how many chunks of each type there are
+    #[clap(long)]
+    pub summary: bool,
+    /// Output format; --detailed and --summary only apply to text
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    /// Warn about chunks with a wrong CRC instead of failing
+    #[clap(long)]
+    pub ignore_crc: bool,
+    /// Only show chunks of this kind
+    #[clap(long, value_enum)]
+    pub only: Option<ChunkFilter>,
+}
+
+#[derive(Parser)]
+pub struct ModifyArgs {
+    pub file_path: PathBuf,
+    pub chunk_type: ChunkType,
+    pub message: String,
+    /// Write the resulting PNG to stdout instead of back to the file
+    #[clap(long)]
+    pub stdout: bool,
+}
+
+#[derive(Parser)]
+pub struct FixCrcArgs {
+    pub file_path: PathBuf,
+    /// Write the resulting PNG to stdout instead of back to the file
+    #[clap(long)]
+    pub stdout: bool,
+}
+
+#[derive(Parser)]
+pub struct CountArgs {
+    pub file_path: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct TrailerArgs {
+    pub file_path: PathBuf,
+    /// Write the trailing bytes to this file (`-` for stdout) instead of
+    /// dumping them as hex
+    #[clap(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Rewrite the file without the trailing bytes
+    #[clap(long, conflicts_with = "output")]
+    pub strip_trailer: bool,
+}
+
+#[derive(Parser)]
+pub struct ValidateArgs {
+    pub file_path: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct VerifyArgs {
+    pub file_path: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct CompletionsArgs {
+    /// Shell to print the completion script for
+    pub shell: Shell,
+}
+
+#[derive(Parser)]
+pub struct BatchEncodeArgs {
+    pub chunk_type: ChunkType,
+    pub message: String,
+    #[clap(required = true)]
+    pub files: Vec<PathBuf>,
+    /// Write the results into this directory instead of back to each file
+    #[clap(long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+    /// Number of files to process at once; defaults to one per CPU
+    #[clap(long, short, value_name = "N")]
+    pub jobs: Option<usize>,
+}
+
+#[derive(Parser)]
+pub struct ExtractArgs {
+    pub file_path: PathBuf,
+    pub chunk_type: ChunkType,
+    /// File to write the chunk's raw data to, or `-` for stdout
+    pub output_file: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct GraftArgs {
+    /// PNG to copy the chunk from
+    pub source: PathBuf,
+    /// PNG to append the chunk to; it is rewritten in place
+    pub destination: PathBuf,
+    pub chunk_type: ChunkType,
+}
+
+#[derive(Parser)]
+pub struct DiffArgs {
+    pub old: PathBuf,
+    pub new: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct ScanArgs {
+    /// Directory to look for `.png` files in
+    pub dir: PathBuf,
+    /// Also scan subdirectories
+    #[clap(long, short)]
+    pub recursive: bool,
+    /// Only report chunks of this kind
+    #[clap(long, value_enum)]
+    pub only: Option<ChunkFilter>,
+}
+
+#[derive(Parser)]
+pub struct InfoArgs {
+    pub file_path: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct TextArgs {
+    pub file_path: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct SetTextArgs {
+    pub file_path: PathBuf,
+    /// 1-79 Latin-1 characters, e.g. `Title` or `Author`
+    pub keyword: String,
+    pub value: String,
 }
@@ -1,10 +1,15 @@
-use std::fmt::{self, Display};
-use std::io::{BufReader, Read};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+#[cfg(feature = "std")]
+use std::io::{ErrorKind, Read};
 use thiserror::Error;
 
 use crate::chunk_type::{ChunkType, ChunkTypeError};
 
-#[derive(Debug)]
+pub(crate) const CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Chunk {
     length: u32,
     chunk_type: ChunkType,
@@ -14,62 +19,216 @@ pub struct Chunk {
 
 #[derive(Debug, Error)]
 pub enum ChunkError {
-    #[error("invalid CRC value")]
-    InvalidCRC,
+    #[error("invalid CRC value: expected {expected:08x}, found {actual:08x}")]
+    InvalidCRC { expected: u32, actual: u32 },
     #[error("invalid Chunk type")]
     InvalidChunkType(#[from] ChunkTypeError),
+    #[cfg(feature = "std")]
     #[error("error while generating from invalid bytes")]
     InvalidBytes(#[from] std::io::Error),
+    #[error("chunk length {0} exceeds the maximum of {max}", max = Chunk::MAX_LENGTH)]
+    LengthTooLarge(u32),
+    #[error("chunk data of {0} bytes exceeds the maximum of {max}", max = Chunk::MAX_LENGTH)]
+    DataTooLarge(usize),
+    #[error("chunk declares {length} bytes of data but only {available} are available")]
+    Truncated { length: u32, available: usize },
+    #[error("input is {0} bytes, but a chunk needs at least {min}", min = Chunk::MIN_SIZE)]
+    TooShort(usize),
+    #[error("chunk data is not followed by a CRC")]
+    MissingCrc,
+}
+
+/// How forgiving parsing is about malformed chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Reject chunks whose stored CRC doesn't match their contents.
+    pub verify_crc: bool,
+    /// Reject chunks whose type isn't valid. Otherwise any four type bytes
+    /// are kept, and `Chunk::has_valid_type` tells whether they are valid.
+    pub strict_type: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            verify_crc: true,
+            strict_type: false,
+        }
+    }
 }
 
 impl TryFrom<&[u8]> for Chunk {
     type Error = ChunkError;
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let mut reader = BufReader::new(value);
+        Chunk::try_from_lenient(value, true)
+    }
+}
+
+impl Chunk {
+    /// Largest data length the PNG specification allows for a chunk.
+    pub const MAX_LENGTH: u32 = (1 << 31) - 1;
+    /// Size of a chunk without data: its length, type and CRC fields.
+    pub const MIN_SIZE: usize = 12;
+
+    /// Reads a single chunk from `reader`, consuming exactly its length,
+    /// type, data and CRC fields.
+    #[cfg(feature = "std")]
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<Chunk, ChunkError> {
+        Self::from_reader_lenient(reader, true)
+    }
+    /// Like `try_from`, but with `verify_crc == false` a chunk whose stored
+    /// CRC doesn't match its contents is kept as-is instead of rejected.
+    pub fn try_from_lenient(value: &[u8], verify_crc: bool) -> Result<Chunk, ChunkError> {
+        Self::try_from_with(
+            value,
+            ParseOptions {
+                verify_crc,
+                ..Default::default()
+            },
+        )
+    }
+    pub fn try_from_with(value: &[u8], options: ParseOptions) -> Result<Chunk, ChunkError> {
+        if value.len() < Self::MIN_SIZE {
+            return Err(ChunkError::TooShort(value.len()));
+        }
+        Self::from_slice_with(value, options)
+    }
+    /// Parses the chunk at the start of `value` by slicing it, ignoring
+    /// anything after its CRC.
+    fn from_slice_with(value: &[u8], options: ParseOptions) -> Result<Chunk, ChunkError> {
+        let length = u32::from_be_bytes(field(value, 0));
+        if length > Self::MAX_LENGTH {
+            return Err(ChunkError::LengthTooLarge(length));
+        }
+        let chunk_type = ChunkType::from_raw(field(value, 4));
+        if options.strict_type {
+            chunk_type.validate()?;
+        }
+
+        let rest = &value[8..];
+        let data = rest.get(..length as usize).ok_or(ChunkError::Truncated {
+            length,
+            available: rest.len(),
+        })?;
+        let crc_start = 8 + data.len();
+        if value.len() < crc_start + 4 {
+            return Err(ChunkError::MissingCrc);
+        }
+        let crc = u32::from_be_bytes(field(value, crc_start));
+
+        Self::from_parts_with(chunk_type, data.to_vec(), crc, options)
+    }
+    #[cfg(feature = "std")]
+    pub fn from_reader_lenient<R: Read>(
+        reader: &mut R,
+        verify_crc: bool,
+    ) -> Result<Chunk, ChunkError> {
+        Self::from_reader_with(
+            reader,
+            ParseOptions {
+                verify_crc,
+                ..Default::default()
+            },
+        )
+    }
+    #[cfg(feature = "std")]
+    pub fn from_reader_with<R: Read>(
+        reader: &mut R,
+        options: ParseOptions,
+    ) -> Result<Chunk, ChunkError> {
         let mut buf = [0; 4];
         reader.read_exact(&mut buf)?;
         let length = u32::from_be_bytes(buf);
+        if length > Self::MAX_LENGTH {
+            return Err(ChunkError::LengthTooLarge(length));
+        }
         reader.read_exact(&mut buf)?;
-        let chunk_type = match ChunkType::try_from(buf) {
-            Ok(chunk_type) => chunk_type,
-            Err(e) => return Err(ChunkError::InvalidChunkType(e)),
-        };
+        let chunk_type = ChunkType::from_raw(buf);
+        if options.strict_type {
+            chunk_type.validate()?;
+        }
 
-        let mut data = vec![0; length as usize];
-        reader.read_exact(&mut data)?;
+        // let the buffer grow as data arrives instead of allocating whatever
+        // the length field claims up front
+        let mut data = Vec::new();
+        reader.by_ref().take(length as u64).read_to_end(&mut data)?;
+        if data.len() != length as usize {
+            return Err(ChunkError::Truncated {
+                length,
+                available: data.len(),
+            });
+        }
 
-        // CSC algorithm CRC-32/ISO-HDLC
-        // width=32 poly=0x04c11db7 init=0xffffffff refin=true refout=true xorout=0xffffffff check=0xcbf43926 residue=0xdebb20e3 name="CRC-32/ISO-HDLC"
         reader.read_exact(&mut buf)?;
         let crc = u32::from_be_bytes(buf);
-        let crc_checker = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
-        let crc_value = crc_checker.checksum(&value[4..(4 + 4 + length) as usize]);
 
-        if crc != crc_value {
-            return Err(ChunkError::InvalidCRC);
+        Self::from_parts_with(chunk_type, data, crc, options)
+    }
+    fn from_parts_with(
+        chunk_type: ChunkType,
+        data: Vec<u8>,
+        crc: u32,
+        options: ParseOptions,
+    ) -> Result<Chunk, ChunkError> {
+        if options.verify_crc {
+            Self::from_parts_checked(chunk_type, data, crc)
+        } else {
+            Self::from_parts(chunk_type, data, crc)
         }
-
-        let res = Self {
-            length,
+    }
+    /// Builds a chunk with the given CRC without checking it. Use this when
+    /// the CRC is known to be correct, e.g. when copying a chunk that was
+    /// already verified, to skip hashing the data. Fails if `data` is
+    /// longer than `MAX_LENGTH`.
+    pub fn from_parts(chunk_type: ChunkType, data: Vec<u8>, crc: u32) -> Result<Chunk, ChunkError> {
+        Ok(Self {
+            length: Self::checked_length(data.len())?,
             chunk_type,
             data,
             crc,
-        };
-        Ok(res)
+        })
     }
-}
-
-impl Chunk {
-    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        let length = data.len() as u32;
-        let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
-        let bytes = [&chunk_type.bytes(), data.as_slice()].concat();
-        let crc = crc.checksum(&bytes);
-        Self {
+    /// Like `from_parts`, but fails if `crc` doesn't match the type and
+    /// data. Use this for CRCs from untrusted input.
+    pub fn from_parts_checked(
+        chunk_type: ChunkType,
+        data: Vec<u8>,
+        crc: u32,
+    ) -> Result<Chunk, ChunkError> {
+        let chunk = Self::from_parts(chunk_type, data, crc)?;
+        if !chunk.verify_crc() {
+            return Err(ChunkError::InvalidCRC {
+                expected: Self::checksum(&chunk.chunk_type, &chunk.data),
+                actual: crc,
+            });
+        }
+        Ok(chunk)
+    }
+    fn checksum(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+        // CSC algorithm CRC-32/ISO-HDLC
+        // width=32 poly=0x04c11db7 init=0xffffffff refin=true refout=true xorout=0xffffffff check=0xcbf43926 residue=0xdebb20e3 name="CRC-32/ISO-HDLC"
+        // feed the type and data separately instead of copying them into
+        // one buffer, which would duplicate large IDAT payloads
+        let mut digest = CRC.digest();
+        digest.update(&chunk_type.bytes());
+        digest.update(data);
+        digest.finalize()
+    }
+    /// Fails if `data` is longer than `MAX_LENGTH`.
+    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Result<Chunk, ChunkError> {
+        let length = Self::checked_length(data.len())?;
+        let crc = Self::checksum(&chunk_type, &data);
+        Ok(Self {
             length,
             chunk_type,
             data,
             crc,
+        })
+    }
+    fn checked_length(len: usize) -> Result<u32, ChunkError> {
+        match u32::try_from(len) {
+            Ok(length) if length <= Self::MAX_LENGTH => Ok(length),
+            _ => Err(ChunkError::DataTooLarge(len)),
         }
     }
     pub fn length(&self) -> u32 {
@@ -84,13 +243,63 @@ impl Chunk {
     pub fn crc(&self) -> u32 {
         self.crc
     }
+    /// Recomputes the CRC over the chunk type and data and compares it to
+    /// the stored one.
+    pub fn verify_crc(&self) -> bool {
+        Self::checksum(&self.chunk_type, &self.data) == self.crc
+    }
+    /// Replaces the data, updating the length and CRC to match.
+    /// Fails, leaving the chunk unchanged, if `data` is longer than
+    /// `MAX_LENGTH`.
+    pub fn set_data(&mut self, data: Vec<u8>) -> Result<(), ChunkError> {
+        self.length = Self::checked_length(data.len())?;
+        self.crc = Self::checksum(&self.chunk_type, &data);
+        self.data = data;
+        Ok(())
+    }
+    /// The chunk type as a string, e.g. `"IHDR"`.
+    pub fn type_str(&self) -> String {
+        self.chunk_type.to_string()
+    }
+    /// Whether the type is `chunk_type`, e.g. `"IHDR"`, without building a
+    /// string like comparing against `type_str` does.
+    pub fn has_type(&self, chunk_type: &str) -> bool {
+        self.chunk_type.bytes() == chunk_type.as_bytes()
+    }
+    /// Whether the type is valid; parsing keeps chunks with any type bytes
+    /// unless `ParseOptions::strict_type` is set.
+    pub fn has_valid_type(&self) -> bool {
+        self.chunk_type.is_valid()
+    }
+    /// Checks that the CRC matches and the chunk type is valid, e.g. after
+    /// a chunk was mutated.
+    pub fn is_valid(&self) -> bool {
+        self.verify_crc() && self.chunk_type.is_valid()
+    }
+    /// Number of data bytes, as a `usize` for sizing buffers.
+    pub fn data_len(&self) -> usize {
+        self.data.len()
+    }
+    /// Replaces the stored CRC with the one computed from the chunk's
+    /// contents. Returns whether it changed.
+    pub fn fix_crc(&mut self) -> bool {
+        let crc = Self::checksum(&self.chunk_type, &self.data);
+        let changed = crc != self.crc;
+        self.crc = crc;
+        changed
+    }
+    #[cfg(feature = "std")]
     pub fn data_as_string(&self) -> crate::Result<String> {
         let mut res = String::with_capacity(self.length as usize);
         for &c in self.data.iter() {
-            res.push(char::try_from(c)?);
+            res.push(char::from(c));
         }
         Ok(res)
     }
+    #[cfg(feature = "std")]
+    pub fn data_as_utf8(&self) -> crate::Result<String> {
+        Ok(String::from_utf8(self.data.clone())?)
+    }
     pub fn as_bytes(&self) -> Vec<u8> {
         let length = self.length.to_be_bytes();
         let chunk_type = self.chunk_type.bytes();
@@ -106,12 +315,125 @@ impl Chunk {
     }
 }
 
+/// The four bytes of `value` starting at `start`, which the caller has
+/// checked are there.
+fn field(value: &[u8], start: usize) -> [u8; 4] {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&value[start..start + 4]);
+    bytes
+}
+
+/// Serialized form of a `Chunk`, with the data base64-encoded.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerdeChunk {
+    #[serde(rename = "type")]
+    chunk_type: ChunkType,
+    length: u32,
+    data: String,
+    crc: u32,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chunk {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use base64::Engine;
+        SerdeChunk {
+            chunk_type: self.chunk_type,
+            length: self.length,
+            data: base64::engine::general_purpose::STANDARD.encode(&self.data),
+            crc: self.crc,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Checks the length and CRC against the decoded data.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Chunk {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use base64::Engine;
+        use serde::de::Error;
+        let chunk = SerdeChunk::deserialize(deserializer)?;
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(&chunk.data)
+            .map_err(D::Error::custom)?;
+        if data.len() != chunk.length as usize {
+            return Err(D::Error::custom(format!(
+                "chunk length {} doesn't match its {} bytes of data",
+                chunk.length,
+                data.len()
+            )));
+        }
+        Chunk::from_parts_checked(chunk.chunk_type, data, chunk.crc).map_err(D::Error::custom)
+    }
+}
+
+/// Lazily reads chunks from a stream positioned just after the PNG
+/// signature.
+///
+/// Iteration ends after the `IEND` chunk or at the end of the stream,
+/// whichever comes first. A malformed chunk yields a single `Err`, after
+/// which the iterator returns `None`.
+#[cfg(feature = "std")]
+pub struct ChunkIter<R: Read> {
+    reader: R,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> ChunkIter<R> {
+    pub fn new(reader: R) -> ChunkIter<R> {
+        Self {
+            reader,
+            done: false,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for ChunkIter<R> {
+    type Item = Result<Chunk, ChunkError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        // read one byte ahead so a clean end of stream isn't reported as a
+        // truncated chunk
+        let mut first = [0; 1];
+        let read = loop {
+            match self.reader.read(&mut first) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                res => break res,
+            }
+        };
+        let res = match read {
+            Ok(0) => {
+                self.done = true;
+                return None;
+            }
+            Ok(_) => Chunk::from_reader(&mut first.as_slice().chain(&mut self.reader)),
+            Err(e) => Err(ChunkError::InvalidBytes(e)),
+        };
+        match &res {
+            Ok(chunk) => self.done = chunk.chunk_type().bytes() == *b"IEND",
+            Err(_) => self.done = true,
+        }
+        Some(res)
+    }
+}
+
 impl Display for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if let Ok(data) = self.data_as_string() {
-            write!(f, "{}", data)
-        } else {
-            Err(fmt::Error)
+        write!(f, "{} ({} bytes): ", self.chunk_type, self.length)?;
+        match core::str::from_utf8(&self.data) {
+            Ok(data) => write!(f, "{:?}", data),
+            Err(_) => {
+                for byte in self.data.iter() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -146,7 +468,7 @@ mod tests {
         let data = "This is where your secret message will be!"
             .as_bytes()
             .to_vec();
-        let chunk = Chunk::new(chunk_type, data);
+        let chunk = Chunk::new(chunk_type, data).unwrap();
         assert_eq!(chunk.length(), 42);
         assert_eq!(chunk.crc(), 2882656334);
     }
@@ -163,6 +485,24 @@ mod tests {
         assert_eq!(chunk.chunk_type().to_string(), String::from("RuSt"));
     }
 
+    #[test]
+    fn test_data_len() {
+        let chunk = testing_chunk();
+        assert_eq!(chunk.data_len(), chunk.length() as usize);
+        assert_eq!(chunk.data_len(), chunk.data().len());
+    }
+
+    #[test]
+    fn test_type_str() {
+        let chunk = testing_chunk();
+        assert_eq!(chunk.type_str(), "RuSt");
+        assert_eq!(chunk.type_str(), chunk.chunk_type().to_string());
+        assert!(chunk.has_type("RuSt"));
+        assert!(!chunk.has_type("rust"));
+        assert!(!chunk.has_type("RuStX"));
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn test_chunk_string() {
         let chunk = testing_chunk();
@@ -171,12 +511,31 @@ mod tests {
         assert_eq!(chunk_string, expected_chunk_string);
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunk_utf8() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let message = "café ☕ 🦀";
+        let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec()).unwrap();
+        assert_eq!(chunk.data_as_utf8().unwrap(), message);
+        assert_ne!(chunk.data_as_string().unwrap(), message);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunk_invalid_utf8() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0x63, 0x61, 0xff]).unwrap();
+        assert!(chunk.data_as_utf8().is_err());
+    }
+
     #[test]
     fn test_chunk_crc() {
         let chunk = testing_chunk();
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_valid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -206,6 +565,141 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_chunk_length_exceeds_input() {
+        let chunk_data: Vec<u8> = 0x7000_0000u32
+            .to_be_bytes()
+            .iter()
+            .chain(b"RuSt".iter())
+            .chain(b"tiny".iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+        assert!(matches!(
+            chunk,
+            Err(ChunkError::Truncated {
+                length: 0x7000_0000,
+                available: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_too_short() {
+        assert!(matches!(
+            Chunk::try_from([].as_ref()),
+            Err(ChunkError::TooShort(0))
+        ));
+        let bytes = testing_chunk().as_bytes();
+        assert!(matches!(
+            Chunk::try_from(&bytes[..11]),
+            Err(ChunkError::TooShort(11))
+        ));
+    }
+
+    #[test]
+    fn test_chunk_missing_crc() {
+        let chunk_data = testing_chunk().as_bytes();
+        for end in [4, 8, 30, chunk_data.len() - 1] {
+            assert!(Chunk::try_from(&chunk_data[..end]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_chunk_from_slice_errors() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        let len = chunk_data.len();
+        assert!(matches!(
+            Chunk::try_from(&chunk_data[..len - 2]),
+            Err(ChunkError::MissingCrc)
+        ));
+        assert!(matches!(
+            Chunk::try_from(&chunk_data[..30]),
+            Err(ChunkError::Truncated {
+                length: 42,
+                available: 22
+            })
+        ));
+
+        // bytes after the CRC belong to whatever follows the chunk
+        chunk_data.extend_from_slice(b"IEND");
+        assert_eq!(
+            Chunk::try_from(chunk_data.as_slice()).unwrap(),
+            testing_chunk()
+        );
+    }
+
+    #[test]
+    fn test_chunk_length_over_limit() {
+        let chunk_data: Vec<u8> = u32::MAX
+            .to_be_bytes()
+            .iter()
+            .chain(b"RuSt".iter())
+            .chain(b"data".iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+        assert!(matches!(chunk, Err(ChunkError::LengthTooLarge(u32::MAX))));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunk_from_reader() {
+        let first = testing_chunk();
+        let second = Chunk::new(ChunkType::from_str("TeSt").unwrap(), b"next".to_vec()).unwrap();
+        let bytes = [first.as_bytes(), second.as_bytes()].concat();
+
+        let mut reader = bytes.as_slice();
+        let chunk = Chunk::from_reader(&mut reader).unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "RuSt");
+        assert_eq!(reader, second.as_bytes().as_slice());
+
+        let chunk = Chunk::from_reader(&mut reader).unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "TeSt");
+        assert!(reader.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunk_iter() {
+        let chunk_type = ChunkType::from_str("IEND").unwrap();
+        let iend = Chunk::new(chunk_type, vec![]).unwrap();
+        let after = Chunk::new(ChunkType::from_str("TeSt").unwrap(), b"after".to_vec()).unwrap();
+        let bytes = [
+            testing_chunk().as_bytes(),
+            iend.as_bytes(),
+            after.as_bytes(),
+        ]
+        .concat();
+
+        let types: Vec<String> = ChunkIter::new(bytes.as_slice())
+            .map(|chunk| chunk.unwrap().chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["RuSt", "IEND"]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunk_iter_without_iend() {
+        let bytes = testing_chunk().as_bytes();
+        let mut iter = ChunkIter::new(bytes.as_slice());
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunk_iter_malformed() {
+        let mut bytes = testing_chunk().as_bytes();
+        bytes.extend_from_slice(&[0, 0, 0, 9, b'T', b'e']);
+        let mut iter = ChunkIter::new(bytes.as_slice());
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
     #[test]
     fn test_invalid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -227,6 +721,161 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_lenient_chunk_from_bytes() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        let last = chunk_data.len() - 1;
+        chunk_data[last] ^= 0xff;
+
+        assert!(Chunk::try_from_lenient(&chunk_data, true).is_err());
+        let chunk = Chunk::try_from_lenient(&chunk_data, false).unwrap();
+        assert!(!chunk.verify_crc());
+        assert_eq!(
+            chunk.data_as_string().unwrap(),
+            "This is where your secret message will be!"
+        );
+    }
+
+    #[test]
+    fn test_chunk_with_invalid_type() {
+        let chunk_type = ChunkType::from_str("Rust").unwrap();
+        let chunk_data = Chunk::new(chunk_type, b"hidden".to_vec())
+            .unwrap()
+            .as_bytes();
+        let strict = ParseOptions {
+            strict_type: true,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            Chunk::try_from_with(&chunk_data, strict),
+            Err(ChunkError::InvalidChunkType(ChunkTypeError::ReservedBit))
+        ));
+        let chunk = Chunk::try_from(chunk_data.as_ref()).unwrap();
+        assert_eq!(chunk.chunk_type(), &chunk_type);
+        assert!(!chunk.has_valid_type());
+        assert!(!chunk.is_valid());
+        assert_eq!(chunk.data(), b"hidden");
+    }
+
+    #[test]
+    fn test_fix_crc() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        let last = chunk_data.len() - 1;
+        chunk_data[last] ^= 0xff;
+
+        let mut chunk = Chunk::try_from_lenient(&chunk_data, false).unwrap();
+        assert!(chunk.fix_crc());
+        assert!(chunk.verify_crc());
+        assert_eq!(chunk.crc(), 2882656334);
+        assert!(!chunk.fix_crc());
+    }
+
+    #[test]
+    fn test_new_chunk_crc_matches_concatenated_checksum() {
+        let chunk_type = ChunkType::from_str("IDAT").unwrap();
+        let data: Vec<u8> = (0..100_000).map(|i| (i % 253) as u8).collect();
+        let expected = CRC.checksum(&[&chunk_type.bytes(), data.as_slice()].concat());
+        assert_eq!(Chunk::new(chunk_type, data).unwrap().crc(), expected);
+        assert_eq!(testing_chunk().crc(), 2882656334);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_parsed_chunk_crc_matches_concatenated_checksum() {
+        let chunk = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![0xa5; 100_000]).unwrap();
+        let expected = CRC.checksum(&chunk.as_bytes()[4..chunk.as_bytes().len() - 4]);
+        let parsed = Chunk::from_reader(&mut chunk.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed.crc(), expected);
+        assert!(parsed.verify_crc());
+    }
+
+    #[test]
+    fn test_chunk_from_parts() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data = b"This is where your secret message will be!".to_vec();
+
+        let chunk = Chunk::from_parts(chunk_type, data.clone(), 2882656334).unwrap();
+        assert_eq!(chunk, testing_chunk());
+        let chunk = Chunk::from_parts(chunk_type, data.clone(), 0).unwrap();
+        assert_eq!(chunk.crc(), 0);
+        assert_eq!(chunk.length(), 42);
+
+        assert_eq!(
+            Chunk::from_parts_checked(chunk_type, data.clone(), 2882656334).unwrap(),
+            testing_chunk()
+        );
+        assert!(matches!(
+            Chunk::from_parts_checked(chunk_type, data, 0),
+            Err(ChunkError::InvalidCRC {
+                expected: 2882656334,
+                actual: 0
+            })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_serde() {
+        let json = serde_json::to_value(testing_chunk()).unwrap();
+        assert_eq!(json["type"], "RuSt");
+        assert_eq!(json["length"], 42);
+        assert_eq!(json["crc"], 2882656334u32);
+        let chunk: Chunk = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(chunk, testing_chunk());
+
+        let mut bad_crc = json.clone();
+        bad_crc["crc"] = 0.into();
+        assert!(serde_json::from_value::<Chunk>(bad_crc).is_err());
+        let mut bad_length = json;
+        bad_length["length"] = 41.into();
+        assert!(serde_json::from_value::<Chunk>(bad_length).is_err());
+    }
+
+    #[test]
+    fn test_chunk_length_checked() {
+        let max = Chunk::MAX_LENGTH as usize;
+        assert_eq!(Chunk::checked_length(0).unwrap(), 0);
+        assert_eq!(Chunk::checked_length(max).unwrap(), Chunk::MAX_LENGTH);
+        // lengths that would have wrapped or overflowed the u32 field
+        for len in [max + 1, u32::MAX as usize, u32::MAX as usize + 43] {
+            assert!(matches!(
+                Chunk::checked_length(len),
+                Err(ChunkError::DataTooLarge(l)) if l == len
+            ));
+        }
+    }
+
+    #[test]
+    fn test_set_data() {
+        let mut chunk = testing_chunk();
+        chunk.set_data(b"another message".to_vec()).unwrap();
+        assert_eq!(chunk.length(), 15);
+        assert_eq!(chunk.data(), b"another message");
+        assert!(chunk.verify_crc());
+
+        let expected = Chunk::new(*chunk.chunk_type(), b"another message".to_vec()).unwrap();
+        assert_eq!(chunk.crc(), expected.crc());
+        let parsed = Chunk::try_from(chunk.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed, chunk);
+    }
+
+    #[test]
+    fn test_chunk_is_valid() {
+        assert!(testing_chunk().is_valid());
+
+        let mut chunk_data = testing_chunk().as_bytes();
+        let last = chunk_data.len() - 1;
+        chunk_data[last] ^= 0xff;
+        assert!(!Chunk::try_from_lenient(&chunk_data, false)
+            .unwrap()
+            .is_valid());
+
+        let chunk_type = ChunkType::from_str("Rust").unwrap();
+        assert!(!Chunk::new(chunk_type, b"data".to_vec()).unwrap().is_valid());
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -244,6 +893,45 @@ mod tests {
             .collect();
 
         let chunk: Chunk = TryFrom::try_from(chunk_data.as_ref()).unwrap();
-        // let _chunk_string = format!("{}", chunk);
+        let _chunk_string = format!("{}", chunk);
+    }
+
+    #[test]
+    fn test_chunk_clone_eq() {
+        let chunk = testing_chunk();
+        let cloned = chunk.clone();
+        assert_eq!(chunk, cloned);
+
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let other = Chunk::new(
+            chunk_type,
+            b"This is where your public message will be!".to_vec(),
+        )
+        .unwrap();
+        assert_eq!(other.length(), chunk.length());
+        assert_ne!(chunk, other);
+    }
+
+    #[test]
+    fn test_chunk_display() {
+        let chunk = testing_chunk();
+        assert_eq!(
+            chunk.to_string(),
+            "RuSt (42 bytes): \"This is where your secret message will be!\""
+        );
+    }
+
+    #[test]
+    fn test_chunk_display_utf8() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::new(chunk_type, "café".as_bytes().to_vec()).unwrap();
+        assert_eq!(chunk.to_string(), "RuSt (5 bytes): \"café\"");
+    }
+
+    #[test]
+    fn test_chunk_display_binary_data() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0x00, 0xff, 0x7f, 0x80]).unwrap();
+        assert_eq!(chunk.to_string(), "RuSt (4 bytes): 00ff7f80");
     }
 }
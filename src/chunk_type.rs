@@ -1,7 +1,11 @@
-use std::{fmt::Display, str::FromStr};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::{fmt::Display, str::FromStr};
+#[cfg(feature = "random-type")]
+use rand::Rng;
 use thiserror::Error;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChunkType {
     codes: [u8; 4],
 }
@@ -10,64 +14,122 @@ pub struct ChunkType {
 pub enum ChunkTypeError {
     #[error("using Reserved Bit")]
     ReservedBit,
-    #[error("include invalid byte")]
-    InvalidByte,
+    #[error("invalid chunk type '{0}': must be 4 ASCII letters")]
+    InvalidByte(String),
+    #[error("chunk type must be 4 bytes long, got {0}")]
+    WrongLength(usize),
+    #[error("chunk type must only contain ASCII letters")]
+    NonAscii,
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
     type Error = ChunkTypeError;
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
-        let res = Self { codes: value };
-        if !res.is_reserved_bit_valid() {
-            Err(ChunkTypeError::ReservedBit)
-        } else if !res.is_only_alphabetic() {
-            Err(ChunkTypeError::InvalidByte)
-        } else {
-            Ok(res)
-        }
+        let res = Self::from_raw(value);
+        res.validate()?;
+        Ok(res)
     }
 }
 
 impl FromStr for ChunkType {
     type Err = ChunkTypeError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.chars().all(|c| c.is_alphabetic()) {
+        if !s.is_ascii() {
+            return Err(ChunkTypeError::NonAscii);
+        }
+        if s.len() != 4 {
+            return Err(ChunkTypeError::WrongLength(s.len()));
+        }
+        if s.chars().all(|c| c.is_ascii_alphabetic()) {
             let bytes = s.as_bytes();
             let codes: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
             let chunktype = Self { codes };
             Ok(chunktype)
         } else {
-            Err(ChunkTypeError::InvalidByte)
+            Err(ChunkTypeError::InvalidByte(s.to_string()))
         }
     }
 }
 
+/// Bytes that aren't printable ASCII, which only a type from `from_raw` can
+/// have, are shown escaped.
 impl Display for ChunkType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let codes_str = String::from_utf8(self.codes.to_vec()).unwrap();
-        write!(f, "{}", codes_str)
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.codes.escape_ascii())
     }
 }
 
-impl PartialEq for ChunkType {
-    fn eq(&self, other: &ChunkType) -> bool {
-        self.codes
-            .iter()
-            .zip(other.codes.iter())
-            .all(|(c1, c2)| c1 == c2)
+/// Serialized as its four-letter string.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
     }
 }
 
-impl Eq for ChunkType {}
-
-impl Clone for ChunkType {
-    fn clone(&self) -> Self {
-        let codes = self.codes;
-        ChunkType { codes }
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChunkType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        ChunkType::from_str(&s).map_err(serde::de::Error::custom)
     }
 }
 
 impl ChunkType {
+    /// Chunk types defined by the PNG specification and its registered
+    /// extensions.
+    pub const STANDARD: [&'static str; 25] = [
+        "IHDR", "PLTE", "IDAT", "IEND", "tRNS", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP",
+        "mDCv", "cLLI", "tEXt", "zTXt", "iTXt", "bKGD", "hIST", "pHYs", "sPLT", "eXIf", "tIME",
+        "acTL", "fcTL", "fdAT",
+    ];
+
+    /// Generates a random ancillary, private, safe-to-copy chunk type,
+    /// i.e. one matching the case pattern `aaAa`.
+    #[cfg(feature = "random-type")]
+    pub fn random_private() -> ChunkType {
+        let mut rng = rand::thread_rng();
+        let mut codes: [u8; 4] = core::array::from_fn(|_| rng.gen_range(b'a'..=b'z'));
+        codes[2].make_ascii_uppercase();
+        Self { codes }
+    }
+    /// Builds a private, safe-to-copy chunk type from a single letter.
+    ///
+    /// `first` is kept as given, so its case decides whether the chunk is
+    /// critical. The remaining bytes repeat the letter with the case the
+    /// private, reserved and safe-to-copy bits require, e.g. `'r'` becomes
+    /// `rrRr`.
+    pub fn new_private_safe(first: char) -> Result<ChunkType, ChunkTypeError> {
+        if !first.is_ascii() {
+            return Err(ChunkTypeError::NonAscii);
+        }
+        if !first.is_ascii_alphabetic() {
+            return Err(ChunkTypeError::InvalidByte(first.to_string()));
+        }
+        let first = first as u8;
+        let lower = first.to_ascii_lowercase();
+        let upper = first.to_ascii_uppercase();
+        Ok(Self {
+            codes: [first, lower, upper, lower],
+        })
+    }
+    /// Wraps any four bytes without validating them, so a chunk with a
+    /// malformed type can still be read. Check the result with `is_valid`.
+    pub fn from_raw(bytes: [u8; 4]) -> ChunkType {
+        Self { codes: bytes }
+    }
+    /// Like `is_valid`, but tells what is wrong.
+    pub fn validate(&self) -> Result<(), ChunkTypeError> {
+        if !self.is_reserved_bit_valid() {
+            Err(ChunkTypeError::ReservedBit)
+        } else if !self.is_only_alphabetic() {
+            Err(ChunkTypeError::InvalidByte(
+                self.codes.escape_ascii().to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
     pub fn bytes(&self) -> [u8; 4] {
         self.codes
     }
@@ -86,6 +148,37 @@ impl ChunkType {
     pub fn is_safe_to_copy(&self) -> bool {
         self.codes[3].is_ascii_lowercase()
     }
+    /// The critical, public, reserved-bit-valid and safe-to-copy properties,
+    /// in that order.
+    pub fn property_bits(&self) -> [bool; 4] {
+        [
+            self.is_critical(),
+            self.is_public(),
+            self.is_reserved_bit_valid(),
+            self.is_safe_to_copy(),
+        ]
+    }
+    /// The properties spelled out, e.g.
+    /// `"critical, public, reserved-valid, unsafe-to-copy"`.
+    pub fn property_string(&self) -> String {
+        const NAMES: [(&str, &str); 4] = [
+            ("critical", "ancillary"),
+            ("public", "private"),
+            ("reserved-valid", "reserved-invalid"),
+            ("safe-to-copy", "unsafe-to-copy"),
+        ];
+        let names: Vec<&str> = self
+            .property_bits()
+            .iter()
+            .zip(NAMES)
+            .map(|(&bit, (set, unset))| if bit { set } else { unset })
+            .collect();
+        names.join(", ")
+    }
+    /// Whether this is one of the `STANDARD` chunk types.
+    pub fn is_standard(&self) -> bool {
+        Self::STANDARD.iter().any(|t| t.as_bytes() == self.codes)
+    }
     fn is_only_alphabetic(&self) -> bool {
         self.codes.iter().all(|byte| byte.is_ascii_alphabetic())
     }
@@ -105,6 +198,16 @@ mod tests {
         assert_eq!(expected, actual.bytes());
     }
 
+    #[test]
+    pub fn test_chunk_type_from_raw() {
+        let chunk_type = ChunkType::from_raw([b'R', 0xff, b'S', b'\n']);
+        assert!(!chunk_type.is_valid());
+        assert!(chunk_type.validate().is_err());
+        assert_eq!(chunk_type.bytes(), [b'R', 0xff, b'S', b'\n']);
+        assert_eq!(chunk_type.to_string(), "R\\xffS\\n");
+        assert!(ChunkType::from_raw(*b"RuSt").validate().is_ok());
+    }
+
     #[test]
     pub fn test_chunk_type_from_str() {
         let expected = ChunkType::try_from([82, 117, 83, 116]).unwrap();
@@ -112,6 +215,32 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    pub fn test_chunk_type_from_str_too_short() {
+        let chunk = ChunkType::from_str("Ru");
+        assert!(matches!(chunk, Err(ChunkTypeError::WrongLength(2))));
+    }
+
+    #[test]
+    pub fn test_chunk_type_from_str_too_long() {
+        let chunk = ChunkType::from_str("RuStx");
+        assert!(matches!(chunk, Err(ChunkTypeError::WrongLength(5))));
+    }
+
+    #[test]
+    pub fn test_chunk_type_from_str_multibyte() {
+        assert!(ChunkType::from_str("Rüst").is_err());
+        assert!(ChunkType::from_str("Rüs").is_err());
+    }
+
+    #[test]
+    pub fn test_chunk_type_from_str_non_ascii() {
+        for s in ["café", "Rüs", "руст", "Ruéé"] {
+            let chunk = ChunkType::from_str(s);
+            assert!(matches!(chunk, Err(ChunkTypeError::NonAscii)), "{s}");
+        }
+    }
+
     #[test]
     pub fn test_chunk_type_is_critical() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -175,12 +304,125 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[cfg(feature = "random-type")]
+    #[test]
+    pub fn test_random_private_chunk_type() {
+        for _ in 0..100 {
+            let chunk = ChunkType::random_private();
+            assert!(chunk.is_valid());
+            assert!(!chunk.is_critical());
+            assert!(!chunk.is_public());
+            assert!(chunk.is_safe_to_copy());
+        }
+    }
+
+    #[test]
+    pub fn test_new_private_safe() {
+        let chunk = ChunkType::new_private_safe('r').unwrap();
+        assert_eq!(&chunk.to_string(), "rrRr");
+        assert!(chunk.is_valid());
+        assert!(!chunk.is_critical());
+        assert!(!chunk.is_public());
+        assert!(chunk.is_safe_to_copy());
+
+        let chunk = ChunkType::new_private_safe('R').unwrap();
+        assert_eq!(&chunk.to_string(), "RrRr");
+        assert!(chunk.is_critical());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn test_chunk_type_serde() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let json = serde_json::to_string(&chunk_type).unwrap();
+        assert_eq!(json, "\"RuSt\"");
+        assert_eq!(
+            serde_json::from_str::<ChunkType>(&json).unwrap(),
+            chunk_type
+        );
+        assert!(serde_json::from_str::<ChunkType>("\"Ru1t\"").is_err());
+    }
+
+    #[test]
+    pub fn test_property_bits() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert_eq!(chunk.property_bits(), [true, false, true, true]);
+        let chunk = ChunkType::from_str("IDAT").unwrap();
+        assert_eq!(chunk.property_bits(), [true, true, true, false]);
+    }
+
+    #[test]
+    pub fn test_property_string() {
+        let chunk = ChunkType::from_str("IDAT").unwrap();
+        assert_eq!(
+            chunk.property_string(),
+            "critical, public, reserved-valid, unsafe-to-copy"
+        );
+        let chunk = ChunkType::from_str("ruse").unwrap();
+        assert_eq!(
+            chunk.property_string(),
+            "ancillary, private, reserved-invalid, safe-to-copy"
+        );
+    }
+
+    #[test]
+    pub fn test_chunk_type_is_standard() {
+        assert!(ChunkType::from_str("IDAT").unwrap().is_standard());
+        assert!(ChunkType::from_str("tEXt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("TEXT").unwrap().is_standard());
+        assert!(!ChunkType::from_str("RuSt").unwrap().is_standard());
+    }
+
+    #[test]
+    pub fn test_invalid_byte_message() {
+        let err = ChunkType::from_str("Ru1t").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid chunk type 'Ru1t': must be 4 ASCII letters"
+        );
+        let err = ChunkType::try_from([b'R', 0, b'S', b't']).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid chunk type 'R\\x00St': must be 4 ASCII letters"
+        );
+    }
+
+    #[test]
+    pub fn test_new_private_safe_invalid() {
+        assert!(matches!(
+            ChunkType::new_private_safe('1'),
+            Err(ChunkTypeError::InvalidByte(_))
+        ));
+        assert!(matches!(
+            ChunkType::new_private_safe('é'),
+            Err(ChunkTypeError::NonAscii)
+        ));
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_chunk_type_copy_hash() {
+        use std::collections::HashMap;
+
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let copied = chunk_type;
+        assert_eq!(chunk_type, copied);
+
+        let mut counts = HashMap::new();
+        *counts.entry(chunk_type).or_insert(0) += 1;
+        *counts.entry(copied).or_insert(0) += 1;
+        *counts
+            .entry(ChunkType::from_str("ruSt").unwrap())
+            .or_insert(0) += 1;
+        assert_eq!(counts[&chunk_type], 2);
+        assert_eq!(counts.len(), 2);
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
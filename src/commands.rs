@@ -1,56 +1,1770 @@
-use clap::Parser;
+use anstyle::{AnsiColor, Style};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use clap::{CommandFactory, Parser};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
 use std::error::Error;
+use std::ffi::OsString;
+use std::fmt::{self, Display};
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
-use crate::args::{DecodeArgs, EncodeArgs, PngMeArgs, PrintArgs, RemoveArgs};
-use crate::chunk::Chunk;
+use crate::args::{
+    BatchEncodeArgs, ChunkFilter, ColorMode, CompletionsArgs, CountArgs, DecodeArgs, DiffArgs,
+    EncodeArgs, ExtractArgs, FixCrcArgs, GraftArgs, InfoArgs, ModifyArgs, OutputFormat, PngMeArgs,
+    PrintArgs, RemoveArgs, ScanArgs, SetTextArgs, TextArgs, TrailerArgs, ValidateArgs, VerifyArgs,
+};
+use crate::chunk::{Chunk, ParseOptions};
+use crate::chunk_type::ChunkType;
+use crate::format::HexDump;
 use crate::png::Png;
+use crate::progress::{self, Progress};
+use crate::text::TextChunk;
+use crate::PngMeError;
+use crate::{compression, crypto, decode_message, message_chunks, MessageLookup};
 
 #[derive(Parser)]
 pub struct PngMeCommmands {
+    /// Don't print informational messages such as "success!"
+    #[clap(long, short, global = true)]
+    pub quiet: bool,
+    /// Highlight chunk types and errors with color
+    #[clap(long, global = true, value_enum, default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
     #[clap(subcommand)]
     pub action: PngMeArgs,
 }
 
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Silences the status messages of all commands.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Applies `--color` to everything printed through `anstream`.
+pub fn set_color(mode: ColorMode) {
+    let choice = match mode {
+        ColorMode::Auto => anstream::ColorChoice::Auto,
+        ColorMode::Always => anstream::ColorChoice::Always,
+        ColorMode::Never => anstream::ColorChoice::Never,
+    };
+    choice.write_global();
+}
+
+/// Style of errors and validation failures.
+pub const ERROR_STYLE: Style = AnsiColor::Red.on_default().bold();
+
+/// Critical chunk types stand out from ancillary ones in listings.
+fn type_style(chunk_type: &ChunkType) -> Style {
+    if chunk_type.is_critical() {
+        AnsiColor::Yellow.on_default().bold()
+    } else {
+        AnsiColor::Green.on_default()
+    }
+}
+
+/// Path argument that stands for stdin when reading and stdout when writing.
+const STDIO_PATH: &str = "-";
+
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO_PATH
+}
+
+fn read_png(path: &Path) -> crate::Result<Png> {
+    read_png_lenient(path, ParseOptions::default())
+}
+
+/// With `verify_crc` off, chunks with a bad CRC are kept and reported on
+/// stderr instead of failing the parse; likewise for invalid chunk types
+/// without `strict_type`.
+fn read_png_lenient(path: &Path, options: ParseOptions) -> crate::Result<Png> {
+    let png = parse_png(path, options)?;
+    if !png.trailing_bytes().is_empty() {
+        eprintln!(
+            "warning: {} bytes of trailing data after IEND",
+            png.trailing_bytes().len()
+        );
+    }
+    for (i, chunk) in png.chunks().iter().enumerate() {
+        if !options.verify_crc && !chunk.verify_crc() {
+            eprintln!(
+                "warning: chunk {i} ({}) has an invalid CRC",
+                chunk.chunk_type()
+            );
+        }
+        if !options.strict_type && !chunk.has_valid_type() {
+            eprintln!(
+                "warning: chunk {i} ({}) has an invalid chunk type",
+                chunk.chunk_type()
+            );
+        }
+    }
+    Ok(png)
+}
+
+fn parse_png(path: &Path, options: ParseOptions) -> crate::Result<Png> {
+    if is_stdio(path) {
+        return Png::from_reader_with(&mut io::stdin().lock(), options);
+    }
+    // SAFETY: like any file reader, pngme trusts that the files it is
+    // pointed at aren't truncated while it reads them
+    #[cfg(feature = "mmap")]
+    {
+        unsafe { Png::open_mmap_with(path, options) }.map_err(|e| match e {
+            PngMeError::Io(e) => PngMeError::Io(with_path(path, e)),
+            e => e,
+        })
+    }
+    #[cfg(not(feature = "mmap"))]
+    Png::from_reader_with(&mut open_file(path)?, options)
+}
+
+/// Buffered reader over the file at `path`, reporting progress if it is
+/// large.
+fn open_file(path: &Path) -> io::Result<Box<dyn io::BufRead>> {
+    let file = File::open(path).map_err(|e| with_path(path, e))?;
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if show_progress(len) {
+        let progress = Progress::new("reading", len, io::stderr());
+        let reader = progress::ProgressReader::new(file, progress);
+        Ok(Box::new(io::BufReader::new(reader)))
+    } else {
+        Ok(Box::new(io::BufReader::new(file)))
+    }
+}
+
+/// Whether to report progress on a file of `len` bytes.
+fn show_progress(len: u64) -> bool {
+    !is_quiet() && len >= progress::THRESHOLD
+}
+
+fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    if is_stdio(path) {
+        io::stdin().read_to_end(&mut bytes)?;
+    } else {
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|e| with_path(path, e))?;
+    }
+    Ok(bytes)
+}
+
+fn write_output(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if is_stdio(path) {
+        io::stdout().write_all(bytes)
+    } else {
+        write_atomic(path, |file| {
+            let len = bytes.len() as u64;
+            if show_progress(len) {
+                progress::write_all(
+                    file,
+                    bytes,
+                    &mut Progress::new("writing", len, io::stderr()),
+                )
+            } else {
+                file.write_all(bytes)
+            }
+        })
+        .map_err(|e| with_path(path, e))
+    }
+}
+
+/// Runs `write` on a temporary file next to `path` and only renames it over
+/// `path` once that succeeded, so an interrupted write can't leave a
+/// truncated file behind.
+fn write_atomic<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    // concurrent writes, e.g. from batch-encode, each need their own file
+    static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp_path = path.with_file_name(tmp_name);
+    let res = File::options()
+        .write(true)
+        .create_new(true)
+        .open(&tmp_path)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()
+        })
+        .and_then(|()| {
+            // keep the mode of the file being replaced
+            if let Ok(metadata) = fs::metadata(path) {
+                fs::set_permissions(&tmp_path, metadata.permissions())?;
+            }
+            fs::rename(&tmp_path, path)
+        });
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
+
+/// Copies `path` to `<path>.bak` before it's overwritten. An existing backup
+/// is never replaced; the copy fails instead.
+fn backup(path: &Path) -> io::Result<()> {
+    let mut backup_path = path.as_os_str().to_owned();
+    backup_path.push(".bak");
+    let backup_path = PathBuf::from(backup_path);
+    let mut original = File::open(path).map_err(|e| with_path(path, e))?;
+    let mut copy = File::options()
+        .write(true)
+        .create_new(true)
+        .open(&backup_path)
+        .map_err(|e| with_path(&backup_path, e))?;
+    io::copy(&mut original, &mut copy).map_err(|e| with_path(&backup_path, e))?;
+    Ok(())
+}
+
+/// Adds the path to an IO error so the user knows which file it's about.
+fn with_path(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {e}", path.display()))
+}
+
+/// Writes the PNG followed by any trailing data it was read with.
+fn write_png(path: &Path, png: &Png) -> io::Result<()> {
+    let mut bytes = png.as_bytes();
+    bytes.extend_from_slice(png.trailing_bytes());
+    write_output(path, &bytes)
+}
+
+/// Prints a status message for a command that wrote its result to `output`.
+fn print_status(output: &Path, message: &str) {
+    if is_quiet() {
+        return;
+    }
+    // keep stdout clean when it carries the PNG itself
+    if is_stdio(output) {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
+
 pub fn encode(args: EncodeArgs) -> Result<(), Box<dyn Error>> {
-    let input = fs::read(&args.file_path)?;
-    let output = args.output_file.unwrap_or(args.file_path);
-    let mut png = Png::try_from(input.as_slice())?;
-    let chunk = Chunk::new(args.chunk_type, args.message.into_bytes());
-    png.append_chunk(chunk);
-    let mut output = File::create(output)?;
-    output.write(png.as_bytes().as_slice())?;
-    println!("success!");
+    if let Some(path) = &args.message_file {
+        if is_stdio(path) && is_stdio(&args.file_path) {
+            return Err("the PNG and the message can't both be read from stdin".into());
+        }
+    }
+    let output = match (args.stdout, args.output_file) {
+        (true, _) => PathBuf::from(STDIO_PATH),
+        (false, Some(output)) => output,
+        (false, None) if args.in_place => args.file_path.clone(),
+        (false, None) => {
+            let output = default_output(&args.file_path, args.output_dir.as_deref())?;
+            print_status(&output, &format!("writing to {}", output.display()));
+            output
+        }
+    };
+    if !args.in_place && is_same_file(&args.file_path, &output) {
+        return Err(format!(
+            "{} would overwrite the input; pass --in-place to allow that",
+            output.display()
+        )
+        .into());
+    }
+    let mut data = match (&args.message_file, args.message) {
+        (Some(path), _) => read_input(path)?,
+        (None, message) => message.unwrap_or_default().into_bytes(),
+    };
+    if args.base64 {
+        data = BASE64
+            .decode(data.trim_ascii())
+            .map_err(|e| format!("message is not valid base64: {e}"))?;
+    }
+    // compress first: encrypted data no longer compresses
+    if args.compress {
+        data = compression::compress(&data)?;
+    }
+    if let Some(password) = &args.password {
+        data = crypto::encrypt(&data, password)?;
+    }
+    check_message_len(data.len(), args.max_message_len)?;
+    let chunk_type = match args.chunk_type {
+        Some(chunk_type) => {
+            // decoders refuse files with critical chunks they don't know
+            if chunk_type.is_critical() {
+                eprintln!(
+                    "warning: {chunk_type} is a critical chunk type; \
+                     use a lowercase first letter to make it ancillary"
+                );
+            }
+            chunk_type
+        }
+        None => {
+            let chunk_type = ChunkType::random_private();
+            // even with --quiet: without it the message can't be found again
+            let message = format!("using chunk type {chunk_type}");
+            if is_stdio(&output) {
+                eprintln!("{message}");
+            } else {
+                println!("{message}");
+            }
+            chunk_type
+        }
+    };
+    if args.backup && output == args.file_path && !is_stdio(&output) {
+        backup(&output)?;
+    }
+    let chunk = Chunk::new(chunk_type, data.clone())?;
+    encode_into(&args.file_path, &output, chunk, args.after.as_ref())?;
+    if args.verify {
+        verify_encoded(&output, &chunk_type, &data)?;
+    }
+    print_status(&output, "success!");
     Ok(())
 }
 
-pub fn decode(args: DecodeArgs) -> Result<(), Box<dyn Error>> {
-    let input = fs::read(&args.file_path)?;
-    let png = Png::try_from(input.as_slice())?;
-    let res = png.chunk_by_type(args.chunk_type.to_string().as_str());
-    if let Some(chunk) = res {
-        println!("{chunk}");
+/// Re-reads the PNG written to `output` and checks it holds `message` in
+/// a chunk of type `chunk_type`.
+fn verify_encoded(
+    output: &Path,
+    chunk_type: &ChunkType,
+    message: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let png = read_png(output)?;
+    let found = png
+        .chunks_by_type(&chunk_type.to_string())
+        .into_iter()
+        .any(|chunk| chunk.data() == message);
+    if !found {
+        return Err(format!(
+            "verification failed: {} has no {chunk_type} chunk with the encoded message",
+            output.display(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Where `encode` writes when neither an output file nor `--in-place` is
+/// given: `<dir>/<file name>` with `--output-dir`, else `<stem>.pngme.png`
+/// next to the input. A PNG read from stdin goes to stdout.
+fn default_output(input: &Path, output_dir: Option<&Path>) -> io::Result<PathBuf> {
+    if is_stdio(input) {
+        return Ok(PathBuf::from(STDIO_PATH));
+    }
+    let file_name = input.file_name().ok_or_else(|| {
+        with_path(
+            input,
+            io::Error::new(io::ErrorKind::InvalidInput, "not a file path"),
+        )
+    })?;
+    match output_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir).map_err(|e| with_path(dir, e))?;
+            Ok(dir.join(file_name))
+        }
+        None => {
+            let mut name = input.file_stem().unwrap_or(file_name).to_owned();
+            name.push(".pngme.png");
+            Ok(input.with_file_name(name))
+        }
+    }
+}
+
+/// Whether both paths name the same existing file, e.g. `cat.png` and
+/// `./cat.png`.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    if is_stdio(a) || is_stdio(b) {
+        return false;
+    }
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Rejects data that doesn't fit in a chunk or exceeds the user's limit.
+fn check_message_len(len: usize, max: Option<usize>) -> Result<(), Box<dyn Error>> {
+    if len > Chunk::MAX_LENGTH as usize {
+        return Err(format!(
+            "message is {len} bytes, but a chunk holds at most {} bytes",
+            Chunk::MAX_LENGTH
+        )
+        .into());
+    }
+    match max {
+        Some(max) if len > max => {
+            Err(format!("message is {len} bytes, more than --max-message-len {max}").into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Adds `chunk` to the PNG at `input`, right after the first chunk of type
+/// `after` if given or else before IEND, and writes the result to `output`.
+fn encode_into(
+    input: &Path,
+    output: &Path,
+    chunk: Chunk,
+    after: Option<&ChunkType>,
+) -> crate::Result<()> {
+    let mut png = read_png(input)?;
+    match after {
+        Some(after) => png.insert_chunk_after(&after.to_string(), chunk)?,
+        None => png.append_chunk(chunk),
+    }
+    write_png(output, &png)?;
+    Ok(())
+}
+
+pub fn batch_encode(args: BatchEncodeArgs) -> Result<(), Box<dyn Error>> {
+    let outputs: Vec<PathBuf> = args
+        .files
+        .iter()
+        .map(|path| match (&args.out_dir, path.file_name()) {
+            (Some(dir), Some(name)) => dir.join(name),
+            _ => path.clone(),
+        })
+        .collect();
+    // two jobs writing the same file would race and one result would be lost
+    let mut seen = HashSet::new();
+    for output in &outputs {
+        let key = fs::canonicalize(output).unwrap_or_else(|_| output.clone());
+        if !seen.insert(key) {
+            return Err(format!(
+                "more than one file would be written to {}",
+                output.display()
+            )
+            .into());
+        }
+    }
+    if let Some(dir) = &args.out_dir {
+        std::fs::create_dir_all(dir).map_err(|e| with_path(dir, e))?;
+    }
+    let message = args.message.into_bytes();
+    check_message_len(message.len(), None)?;
+    let chunk = Chunk::new(args.chunk_type, message)?;
+    // 0 lets rayon pick one thread per CPU
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs.unwrap_or(0))
+        .build()?;
+    let failures: Vec<_> = pool.install(|| {
+        args.files
+            .par_iter()
+            .zip(&outputs)
+            .filter_map(|(path, output)| {
+                encode_into(path, output, chunk.clone(), None)
+                    .err()
+                    .map(|e| (path, e))
+            })
+            .collect()
+    });
+    let encoded = args.files.len() - failures.len();
+    if !is_quiet() {
+        println!("encoded {encoded} of {} file(s)", args.files.len());
+    }
+    for (path, e) in &failures {
+        match e {
+            // IO errors already name the file
+            PngMeError::Io(e) => eprintln!("{e}"),
+            e => eprintln!("{}: {e}", path.display()),
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
     } else {
-        println!("chunk type {} is not found.", args.chunk_type.to_string());
+        Err(format!("{} file(s) failed", failures.len()).into())
+    }
+}
+
+pub fn decode(args: DecodeArgs) -> Result<(), Box<dyn Error>> {
+    let png = read_png_lenient(
+        &args.file_path,
+        ParseOptions {
+            verify_crc: !args.ignore_crc,
+            strict_type: !args.lenient_type,
+        },
+    )?;
+    // clap requires the type unless --index is given
+    let chunk_type = args.chunk_type.map(|t| t.to_string()).unwrap_or_default();
+    let lookup = match args.index {
+        Some(i) => MessageLookup::Index(i),
+        None if args.ignore_case => MessageLookup::TypeIgnoringCase(&chunk_type),
+        None => MessageLookup::Type(&chunk_type),
+    };
+    let chunks = message_chunks(&png, lookup, args.all);
+    if chunks.is_empty() {
+        return Err(match args.index {
+            Some(i) => format!(
+                "no chunk at index {i}: the file has {} chunks",
+                png.chunks().len()
+            )
+            .into(),
+            None => PngMeError::ChunkNotFound(chunk_type).into(),
+        });
+    }
+    if args.ignore_case {
+        warn_distinct_casings(&chunks);
+    }
+    let mut records = vec![];
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let decoded;
+        let message = match decode_payload(chunk.data(), args.password.as_deref())? {
+            Some(message) => {
+                decoded = Chunk::new(*chunk.chunk_type(), message)?;
+                &decoded
+            }
+            None => chunk,
+        };
+        match args.format {
+            OutputFormat::Json => records.push(ChunkRecord {
+                message: message.data_as_utf8().ok(),
+                ..ChunkRecord::new(chunk)
+            }),
+            OutputFormat::Text if args.hex => {
+                if args.all {
+                    print!("{}: ", i + 1);
+                }
+                let style = type_style(message.chunk_type());
+                anstream::println!(
+                    "{style}{}{style:#} ({} bytes):",
+                    message.chunk_type(),
+                    message.length()
+                );
+                print!("{}", HexDump(message.data()));
+            }
+            OutputFormat::Text if args.base64 => {
+                if args.all {
+                    print!("{}: ", i + 1);
+                }
+                println!("{}", BASE64.encode(message.data()));
+            }
+            OutputFormat::Text if args.all => println!("{}: {message}", i + 1),
+            OutputFormat::Text => println!("{message}"),
+        }
+    }
+    if args.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&records)?);
     }
     Ok(())
 }
 
+/// Warns if chunks matched ignoring case have different types.
+fn warn_distinct_casings(chunks: &[&Chunk]) {
+    let mut casings = HashSet::new();
+    let types: Vec<String> = chunks
+        .iter()
+        .filter(|c| casings.insert(*c.chunk_type()))
+        .map(|c| c.type_str())
+        .collect();
+    if types.len() > 1 {
+        eprintln!(
+            "warning: {} match ignoring case but are distinct chunk types",
+            types.join(", ")
+        );
+    }
+}
+
+/// Reverses the encryption and compression `encode` may have applied.
+/// Returns `None` when the data was stored as-is.
+fn decode_payload(data: &[u8], password: Option<&str>) -> crate::Result<Option<Vec<u8>>> {
+    let decrypted = match password {
+        Some(password) => Some(crypto::decrypt(data, password)?),
+        None => None,
+    };
+    let data = decrypted.as_deref().unwrap_or(data);
+    if compression::is_compressed(data) {
+        return Ok(Some(compression::decompress(data)?));
+    }
+    Ok(decrypted)
+}
+
+pub fn extract(args: ExtractArgs) -> Result<(), Box<dyn Error>> {
+    let png = read_png(&args.file_path)?;
+    let chunk_type = args.chunk_type.to_string();
+    let data = decode_message(&png, &chunk_type).ok_or(PngMeError::ChunkNotFound(chunk_type))?;
+    write_output(&args.output_file, &data)?;
+    print_status(
+        &args.output_file,
+        &format!("extracted {} bytes", data.len()),
+    );
+    Ok(())
+}
+
+pub fn graft(args: GraftArgs) -> Result<(), Box<dyn Error>> {
+    let source = read_png(&args.source)?;
+    let chunk_type = args.chunk_type.to_string();
+    let bytes = source
+        .serialized_chunk(&chunk_type)
+        .ok_or_else(|| PngMeError::ChunkNotFound(chunk_type.clone()))?;
+    // parsing checks the CRC, and rebuilding the chunk recomputes it
+    let chunk = Chunk::try_from(bytes.as_slice())?;
+    let chunk = Chunk::new(*chunk.chunk_type(), chunk.data().to_vec())?;
+    let mut png = read_png(&args.destination)?;
+    png.append_chunk(chunk);
+    write_png(&args.destination, &png)?;
+    print_status(
+        &args.destination,
+        &format!("grafted {chunk_type} into {}", args.destination.display()),
+    );
+    Ok(())
+}
+
 pub fn remove(args: RemoveArgs) -> Result<(), Box<dyn Error>> {
-    let input = fs::read(&args.file_path)?;
-    let mut png = Png::try_from(input.as_slice())?;
-    png.remove_chunk(args.chunk_type.to_string().as_str())?;
-    let mut output = File::create(args.file_path)?;
-    output.write(&png.as_bytes())?;
+    let mut png = read_png(&args.file_path)?;
+    let chunk_type = args.chunk_type.to_string();
+    if args.dry_run {
+        return print_removal_plan(&png, &chunk_type, &args);
+    }
+    // decoders can't render an image that lacks one of its critical chunks
+    if args.chunk_type.is_critical() && !args.force {
+        return Err(format!(
+            "refusing to remove critical chunk {chunk_type}; pass --force to remove it anyway"
+        )
+        .into());
+    }
+    let output = if args.stdout {
+        Path::new(STDIO_PATH)
+    } else {
+        &args.file_path
+    };
+    if args.all {
+        let count = png.remove_all_chunks(&chunk_type);
+        if count == 0 {
+            return Err(PngMeError::ChunkNotFound(chunk_type).into());
+        }
+        if args.backup && !is_stdio(output) {
+            backup(output)?;
+        }
+        write_png(output, &png)?;
+        print_status(output, &format!("removed {count} chunk(s)"));
+        return Ok(());
+    }
+    let removed = match args.index {
+        Some(n) => png.remove_chunk_at(&chunk_type, n)?,
+        None => png.remove_chunk(&chunk_type)?,
+    };
+    if args.backup && !is_stdio(output) {
+        backup(output)?;
+    }
+    write_png(output, &png)?;
+    if args.print {
+        // keep stdout clean when it carries the PNG itself
+        if is_stdio(output) {
+            eprintln!("removed {removed}");
+        } else {
+            println!("removed {removed}");
+        }
+    }
     Ok(())
 }
 
-pub fn print(args: PrintArgs) -> Result<(), Box<dyn Error>> {
-    let input = fs::read(&args.file_path)?;
-    let png = Png::try_from(input.as_slice())?;
-    png.chunks()
+/// Reports which chunks `remove` would remove without touching the file.
+fn print_removal_plan(
+    png: &Png,
+    chunk_type: &str,
+    args: &RemoveArgs,
+) -> Result<(), Box<dyn Error>> {
+    let count = png.count_chunks(chunk_type);
+    let positions: Vec<usize> = png
+        .chunks()
         .iter()
-        .for_each(|c| println!("{}", c.chunk_type().to_string()));
+        .enumerate()
+        .filter(|(_, c)| c.has_type(chunk_type))
+        .map(|(i, _)| i)
+        .collect();
+    let positions = match (args.all, args.index) {
+        (true, _) => positions,
+        (false, n) => positions.get(n.unwrap_or(0)).copied().into_iter().collect(),
+    };
+    if positions.is_empty() {
+        return Err(PngMeError::ChunkNotFound(chunk_type.to_string()).into());
+    }
+    let positions: Vec<String> = positions.iter().map(|i| i.to_string()).collect();
+    println!(
+        "would remove {} of {count} {chunk_type} chunk(s), at position(s) {}",
+        positions.len(),
+        positions.join(", ")
+    );
+    Ok(())
+}
+
+pub fn modify(args: ModifyArgs) -> Result<(), Box<dyn Error>> {
+    let mut png = read_png(&args.file_path)?;
+    let chunk = Chunk::new(args.chunk_type, args.message.into_bytes())?;
+    png.replace_chunk(&chunk.type_str(), chunk)?;
+    let output = if args.stdout {
+        Path::new(STDIO_PATH)
+    } else {
+        &args.file_path
+    };
+    write_png(output, &png)?;
+    Ok(())
+}
+
+pub fn fix_crc(args: FixCrcArgs) -> Result<(), Box<dyn Error>> {
+    let mut png = read_png_lenient(
+        &args.file_path,
+        ParseOptions {
+            verify_crc: false,
+            ..Default::default()
+        },
+    )?;
+    let fixed = png.fix_crcs();
+    let output = if args.stdout {
+        Path::new(STDIO_PATH)
+    } else {
+        &args.file_path
+    };
+    write_png(output, &png)?;
+    print_status(output, &format!("fixed {fixed} CRC(s)"));
+    Ok(())
+}
+
+pub fn count(args: CountArgs) -> Result<(), Box<dyn Error>> {
+    let png = read_png(&args.file_path)?;
+    let chunks = png.chunks();
+    let data_bytes: u64 = chunks.iter().map(|c| c.data_len() as u64).sum();
+    println!("chunks: {}", chunks.len());
+    println!("data bytes: {data_bytes}");
+    if let Some(largest) = chunks.iter().max_by_key(|c| c.length()) {
+        println!(
+            "largest: {} ({} bytes)",
+            largest.chunk_type(),
+            largest.length()
+        );
+    }
+    Ok(())
+}
+
+pub fn trailer(args: TrailerArgs) -> Result<(), Box<dyn Error>> {
+    let png = read_png(&args.file_path)?;
+    let trailer = png.trailing_bytes();
+    if args.strip_trailer {
+        write_output(&args.file_path, &png.as_bytes())?;
+        print_status(
+            &args.file_path,
+            &format!("stripped {} bytes", trailer.len()),
+        );
+    } else if let Some(output) = &args.output {
+        write_output(output, trailer)?;
+    } else if trailer.is_empty() {
+        println!("no trailing data");
+    } else {
+        print!("{}", HexDump(trailer));
+    }
+    Ok(())
+}
+
+pub fn validate(args: ValidateArgs) -> Result<(), Box<dyn Error>> {
+    // CRC mismatches are reported as violations rather than parse errors
+    let png = parse_png(
+        &args.file_path,
+        ParseOptions {
+            verify_crc: false,
+            ..Default::default()
+        },
+    )?;
+    let violations = png.validate();
+    if violations.is_empty() {
+        print_status(&args.file_path, "valid");
+        return Ok(());
+    }
+    for violation in &violations {
+        anstream::println!("{ERROR_STYLE}{violation}{ERROR_STYLE:#}");
+    }
+    Err(format!("found {} violation(s)", violations.len()).into())
+}
+
+pub fn verify(args: VerifyArgs) -> Result<(), Box<dyn Error>> {
+    let report = if is_stdio(&args.file_path) {
+        Png::verify_crcs(&mut io::stdin().lock())?
+    } else {
+        Png::verify_crcs(&mut open_file(&args.file_path)?)?
+    };
+    match report.mismatch {
+        Some(m) => Err(format!(
+            "chunk {} ({}) at offset {} has an invalid CRC: expected {:08x}, found {:08x}",
+            m.index, m.chunk_type, m.offset, m.expected, m.actual
+        )
+        .into()),
+        None => {
+            if report.trailing > 0 {
+                eprintln!(
+                    "warning: {} bytes of trailing data after IEND have no CRC to check",
+                    report.trailing
+                );
+            }
+            print_status(
+                &args.file_path,
+                &format!("all {} chunk CRCs match", report.chunks),
+            );
+            Ok(())
+        }
+    }
+}
+
+pub fn completions(args: CompletionsArgs) -> Result<(), Box<dyn Error>> {
+    let mut command = PngMeCommmands::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(args.shell, &mut command, name, &mut io::stdout());
     Ok(())
 }
+
+pub fn diff(args: DiffArgs) -> Result<(), Box<dyn Error>> {
+    let old = read_png(&args.old)?;
+    let new = read_png(&args.new)?;
+    let diffs = diff_chunks(&old, &new);
+    if diffs.is_empty() {
+        println!("no differences");
+        return Ok(());
+    }
+    for diff in &diffs {
+        println!("{diff}");
+    }
+    let count = |f: fn(&ChunkDiff) -> bool| diffs.iter().filter(|d| f(d)).count();
+    println!(
+        "{} added, {} removed, {} changed",
+        count(|d| matches!(d, ChunkDiff::Added(..))),
+        count(|d| matches!(d, ChunkDiff::Removed(..))),
+        count(|d| matches!(d, ChunkDiff::Changed(..))),
+    );
+    Ok(())
+}
+
+/// Difference in the `n`th chunk of some type between two PNGs.
+#[derive(Debug, PartialEq)]
+enum ChunkDiff<'a> {
+    Added(usize, &'a Chunk),
+    Removed(usize, &'a Chunk),
+    Changed(usize, &'a Chunk, &'a Chunk),
+}
+
+impl Display for ChunkDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkDiff::Added(n, chunk) => write!(
+                f,
+                "+ {} #{n} ({} bytes)",
+                chunk.chunk_type(),
+                chunk.length()
+            ),
+            ChunkDiff::Removed(n, chunk) => write!(
+                f,
+                "- {} #{n} ({} bytes)",
+                chunk.chunk_type(),
+                chunk.length()
+            ),
+            ChunkDiff::Changed(n, old, new) => write!(
+                f,
+                "~ {} #{n}: {} -> {} bytes, crc {:08x} -> {:08x}",
+                old.chunk_type(),
+                old.length(),
+                new.length(),
+                old.crc(),
+                new.crc()
+            ),
+        }
+    }
+}
+
+/// Pairs up the chunks of each type by their position among chunks of that
+/// type, so inserting one chunk doesn't make every later chunk differ.
+fn diff_chunks<'a>(old: &'a Png, new: &'a Png) -> Vec<ChunkDiff<'a>> {
+    let mut types: Vec<ChunkType> = vec![];
+    for chunk in old.chunks().iter().chain(new.chunks()) {
+        if !types.contains(chunk.chunk_type()) {
+            types.push(*chunk.chunk_type());
+        }
+    }
+    let mut diffs = vec![];
+    for chunk_type in types {
+        let old_chunks = old.filter(|c| *c.chunk_type() == chunk_type);
+        let new_chunks = new.filter(|c| *c.chunk_type() == chunk_type);
+        for n in 0..old_chunks.len().max(new_chunks.len()) {
+            match (old_chunks.get(n), new_chunks.get(n)) {
+                (Some(old), Some(new)) if old != new => diffs.push(ChunkDiff::Changed(n, old, new)),
+                (Some(old), None) => diffs.push(ChunkDiff::Removed(n, old)),
+                (None, Some(new)) => diffs.push(ChunkDiff::Added(n, new)),
+                _ => {}
+            }
+        }
+    }
+    diffs
+}
+
+pub fn scan(args: ScanArgs) -> Result<(), Box<dyn Error>> {
+    let mut paths = vec![];
+    find_pngs(&args.dir, args.recursive, &mut paths)?;
+    for path in paths {
+        let png = match read_png(&path) {
+            Ok(png) => png,
+            Err(e) => {
+                eprintln!("warning: skipping {}: {e}", path.display());
+                continue;
+            }
+        };
+        let custom =
+            png.filter(|c| !c.chunk_type().is_standard() && shown(args.only, c.chunk_type()));
+        for chunk in custom {
+            let chunk_type = chunk.chunk_type();
+            println!(
+                "{}: {} ({}, {}, {} bytes) {}",
+                path.display(),
+                chunk_type,
+                if chunk_type.is_public() {
+                    "public"
+                } else {
+                    "private"
+                },
+                if chunk_type.is_critical() {
+                    "critical"
+                } else {
+                    "ancillary"
+                },
+                chunk.length(),
+                preview(chunk.data()),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Collects the `.png` files in `dir`, sorted, descending into
+/// subdirectories if `recursive` is set. Symlinked directories aren't
+/// followed, so a link back up the tree can't make it loop.
+fn find_pngs(dir: &Path, recursive: bool, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<io::Result<Vec<_>>>())
+        .map_err(|e| with_path(dir, e))?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|e| with_path(&path, e))?;
+        if file_type.is_dir() {
+            if recursive {
+                find_pngs(&path, recursive, paths)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Short quoted excerpt of text data, or a note that the data is binary.
+fn preview(data: &[u8]) -> String {
+    const MAX_CHARS: usize = 40;
+    match std::str::from_utf8(data) {
+        Ok(text) if text.chars().count() > MAX_CHARS => {
+            let excerpt: String = text.chars().take(MAX_CHARS).collect();
+            format!("{excerpt:?}...")
+        }
+        Ok(text) => format!("{text:?}"),
+        Err(_) => "(binary)".to_string(),
+    }
+}
+
+pub fn info(args: InfoArgs) -> Result<(), Box<dyn Error>> {
+    let png = read_png(&args.file_path)?;
+    let ihdr = png.ihdr()?;
+    println!("dimensions: {}x{}", ihdr.width, ihdr.height);
+    println!("bit depth: {}", ihdr.bit_depth);
+    println!("color type: {}", ihdr.color_type);
+    println!(
+        "interlace: {}",
+        if ihdr.interlaced { "Adam7" } else { "none" }
+    );
+    Ok(())
+}
+
+pub fn text(args: TextArgs) -> Result<(), Box<dyn Error>> {
+    let png = read_png(&args.file_path)?;
+    let chunks = png.filter(|c| TextChunk::TYPES.contains(&c.type_str().as_str()));
+    for chunk in chunks {
+        match TextChunk::try_from(chunk) {
+            Ok(text) => println!("{}: {}", text.keyword, text.text),
+            Err(e) => eprintln!("warning: skipping {} chunk: {e}", chunk.chunk_type()),
+        }
+    }
+    Ok(())
+}
+
+pub fn set_text(args: SetTextArgs) -> Result<(), Box<dyn Error>> {
+    let mut png = read_png(&args.file_path)?;
+    let chunk = TextChunk::new(&args.keyword, &args.value)?.to_chunk()?;
+    png.append_chunk(chunk);
+    write_png(&args.file_path, &png)?;
+    Ok(())
+}
+
+pub fn print(args: PrintArgs) -> Result<(), Box<dyn Error>> {
+    let png = read_png_lenient(
+        &args.file_path,
+        ParseOptions {
+            verify_crc: !args.ignore_crc,
+            ..Default::default()
+        },
+    )?;
+    let only = args.only;
+    if args.format == OutputFormat::Json {
+        print_json(&png, only)?;
+    } else if args.detailed {
+        print_detailed(&png, only, args.pretty);
+    } else if args.summary {
+        print_summary(&png, only);
+    } else {
+        let offsets = png.chunk_offsets();
+        png.chunks()
+            .iter()
+            .zip(offsets)
+            .filter(|(c, _)| shown(only, c.chunk_type()))
+            .for_each(|(c, offset)| {
+                let style = type_style(c.chunk_type());
+                if args.offsets {
+                    anstream::println!("{offset:>10}  {style}{}{style:#}", c.chunk_type());
+                } else {
+                    anstream::println!("{style}{}{style:#}", c.chunk_type());
+                }
+            });
+    }
+    Ok(())
+}
+
+fn shown(only: Option<ChunkFilter>, chunk_type: &ChunkType) -> bool {
+    only.is_none_or(|only| only.matches(chunk_type))
+}
+
+fn print_detailed(png: &Png, only: Option<ChunkFilter>, pretty: bool) {
+    let signature: Vec<String> = png.header().iter().map(|b| format!("{b:02x}")).collect();
+    println!("signature: {}", signature.join(" "));
+    println!(
+        "{:>10}  {:<4}  {:>10}  {:<8}  {:<9}  {:<7}  {:<8}  SAFE",
+        "OFFSET", "TYPE", "LENGTH", "CRC", "CRITICAL", "PUBLIC", "RESERVED"
+    );
+    for (chunk, offset) in png.chunks().iter().zip(png.chunk_offsets()) {
+        let chunk_type = chunk.chunk_type();
+        if shown(only, chunk_type) {
+            let [critical, public, reserved, safe] = chunk_type.property_bits().map(flag);
+            let style = type_style(chunk_type);
+            anstream::println!(
+                "{:>10}  {style}{:<4}{style:#}  {:>10}  {:08x}  {critical:<9}  {public:<7}  {reserved:<8}  {safe}",
+                offset,
+                chunk_type,
+                chunk.length(),
+                chunk.crc(),
+            );
+            if pretty {
+                print!("{}", HexDump(chunk.data()));
+            }
+        }
+    }
+}
+
+/// Machine-readable description of a chunk for `--format json`.
+#[derive(Serialize)]
+struct ChunkRecord {
+    #[serde(rename = "type")]
+    chunk_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<usize>,
+    length: u32,
+    crc: u32,
+    critical: bool,
+    public: bool,
+    reserved_bit_valid: bool,
+    safe_to_copy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl ChunkRecord {
+    fn new(chunk: &Chunk) -> Self {
+        let chunk_type = chunk.chunk_type();
+        let [critical, public, reserved_bit_valid, safe_to_copy] = chunk_type.property_bits();
+        Self {
+            chunk_type: chunk_type.to_string(),
+            offset: None,
+            length: chunk.length(),
+            crc: chunk.crc(),
+            critical,
+            public,
+            reserved_bit_valid,
+            safe_to_copy,
+            message: None,
+        }
+    }
+}
+
+fn print_json(png: &Png, only: Option<ChunkFilter>) -> Result<(), Box<dyn Error>> {
+    let mut records = vec![];
+    for (chunk, offset) in png.chunks().iter().zip(png.chunk_offsets()) {
+        if shown(only, chunk.chunk_type()) {
+            records.push(ChunkRecord {
+                offset: Some(offset),
+                ..ChunkRecord::new(chunk)
+            });
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&records)?);
+    Ok(())
+}
+
+fn print_summary(png: &Png, only: Option<ChunkFilter>) {
+    let mut counts: Vec<(ChunkType, usize)> = png
+        .chunk_counts()
+        .into_iter()
+        .filter(|(chunk_type, _)| shown(only, chunk_type))
+        .collect();
+    counts.sort_by_key(|(chunk_type, count)| (std::cmp::Reverse(*count), chunk_type.bytes()));
+    for (chunk_type, count) in counts {
+        let style = type_style(&chunk_type);
+        anstream::println!("{style}{chunk_type}{style:#}  {count:>6}");
+    }
+}
+
+fn flag(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pngme-{}-{}", std::process::id(), name))
+    }
+
+    fn chunk(chunk_type: &str, data: impl AsRef<[u8]>) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str(chunk_type).unwrap(),
+            data.as_ref().to_vec(),
+        )
+        .unwrap()
+    }
+
+    /// Bytes of a PNG holding nothing but an `IEND` chunk.
+    fn empty_png() -> Vec<u8> {
+        Png::from_chunks_unchecked(vec![chunk("IEND", "")]).as_bytes()
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        PngMeCommmands::command().debug_assert();
+    }
+
+    #[test]
+    fn test_io_errors_name_the_path() {
+        let path = temp_path("missing.png");
+        let message = read_png(&path).err().unwrap().to_string();
+        assert!(message.starts_with(&path.display().to_string()));
+        let message = read_input(&path).unwrap_err().to_string();
+        assert!(message.starts_with(&path.display().to_string()));
+        let path = path.join("not-a-dir.png");
+        let message = write_output(&path, b"").unwrap_err().to_string();
+        assert!(message.starts_with(&path.display().to_string()));
+    }
+
+    #[test]
+    fn test_write_output_large_png() {
+        let data: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let chunk = chunk("IDAT", data);
+        let png = Png::from_chunks_unchecked(vec![chunk]);
+        let expected = png.as_bytes();
+
+        let path = temp_path("large.png");
+        write_output(&path, &expected).unwrap();
+        let actual = std::fs::read(&path).unwrap();
+        let png = read_png(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(actual, expected);
+        assert_eq!(png.as_bytes(), expected);
+    }
+
+    #[test]
+    fn test_read_input_keeps_binary_bytes() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let path = temp_path("message.bin");
+        std::fs::write(&path, &bytes).unwrap();
+        let actual = read_input(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(actual, bytes);
+    }
+
+    #[test]
+    fn test_encode_binary_message_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let png_path = temp_path("binary.png");
+        let message_path = temp_path("binary.bin");
+        std::fs::write(&png_path, empty_png()).unwrap();
+        std::fs::write(&message_path, &bytes).unwrap();
+
+        encode(EncodeArgs {
+            file_path: png_path.clone(),
+            chunk_type: Some(ChunkType::from_str("ruSt").unwrap()),
+            message: None,
+            output_file: None,
+            message_file: Some(message_path.clone()),
+            stdout: false,
+            password: None,
+            compress: false,
+            base64: false,
+            max_message_len: None,
+            after: None,
+            backup: false,
+            verify: false,
+            in_place: true,
+            output_dir: None,
+            random_type: false,
+        })
+        .unwrap();
+        let png = read_png(&png_path).unwrap();
+        std::fs::remove_file(&png_path).unwrap();
+        std::fs::remove_file(&message_path).unwrap();
+
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), bytes.as_slice());
+    }
+
+    #[test]
+    fn test_decode_payload() {
+        let message = b"squeeze me".repeat(10);
+        let compressed = compression::compress(&message).unwrap();
+        assert_eq!(decode_payload(&compressed, None).unwrap(), Some(message));
+        assert_eq!(decode_payload(b"squeeze me", None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_encode_random_type() {
+        let png_path = temp_path("random-type.png");
+        let message_path = temp_path("random-type.txt");
+        std::fs::write(&png_path, empty_png()).unwrap();
+        std::fs::write(&message_path, "hidden message").unwrap();
+        let parse = |extra: &[&str]| {
+            let mut argv = vec![
+                "pngme",
+                "encode",
+                png_path.to_str().unwrap(),
+                "--random-type",
+            ];
+            argv.extend(extra);
+            PngMeCommmands::try_parse_from(argv).map(|cli| cli.action)
+        };
+
+        let with_type = parse(&["ruSt", "hidden message"]);
+        let without_message = parse(&[]);
+        let parsed = parse(&[
+            "--message-file",
+            message_path.to_str().unwrap(),
+            "--in-place",
+        ]);
+        let Ok(PngMeArgs::Encode(args)) = parsed else {
+            panic!("--random-type with --message-file should parse");
+        };
+        encode(args).unwrap();
+        let png = read_png(&png_path).unwrap();
+        std::fs::remove_file(&png_path).unwrap();
+        std::fs::remove_file(&message_path).unwrap();
+
+        assert!(with_type.is_err());
+        assert!(without_message.is_err());
+        let chunk = &png.chunks()[0];
+        assert_eq!(chunk.data(), b"hidden message");
+        assert!(!chunk.chunk_type().is_critical());
+        assert!(!chunk.chunk_type().is_public());
+    }
+
+    #[test]
+    fn test_default_output() {
+        assert_eq!(
+            default_output(Path::new("images/cat.png"), None).unwrap(),
+            Path::new("images/cat.pngme.png")
+        );
+        assert_eq!(
+            default_output(Path::new("-"), None).unwrap(),
+            Path::new(STDIO_PATH)
+        );
+        let dir = temp_path("output-dir");
+        assert_eq!(
+            default_output(Path::new("images/cat.png"), Some(&dir)).unwrap(),
+            dir.join("cat.png")
+        );
+        assert!(dir.is_dir());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_encode_refuses_to_overwrite_input() {
+        let dir = temp_path("overwrite");
+        std::fs::create_dir_all(&dir).unwrap();
+        let png_path = dir.join("in.png");
+        std::fs::write(&png_path, empty_png()).unwrap();
+        let encode_to = |output_dir: PathBuf, in_place: bool| {
+            encode(EncodeArgs {
+                file_path: png_path.clone(),
+                chunk_type: Some(ChunkType::from_str("ruSt").unwrap()),
+                message: Some("message".to_string()),
+                output_file: None,
+                message_file: None,
+                stdout: false,
+                password: None,
+                compress: false,
+                base64: false,
+                max_message_len: None,
+                after: None,
+                backup: false,
+                verify: false,
+                in_place,
+                output_dir: (!in_place).then_some(output_dir),
+                random_type: false,
+            })
+        };
+
+        let res = encode_to(dir.join("."), false);
+        let unchanged = std::fs::read(&png_path).unwrap();
+        encode_to(dir.clone(), true).unwrap();
+        let encoded = read_png(&png_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(res.is_err());
+        assert_eq!(unchanged, empty_png());
+        assert!(encoded.chunk_by_type("ruSt").is_some());
+    }
+
+    #[test]
+    fn test_verify_encoded() {
+        let path = temp_path("verify.png");
+        let png = Png::from_chunks_unchecked(vec![chunk("ruSt", "old"), chunk("IEND", "")]);
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let missing = verify_encoded(&path, &chunk_type, b"new");
+        encode_into(&path, &path, chunk("ruSt", "new"), None).unwrap();
+        let present = verify_encoded(&path, &chunk_type, b"new");
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(missing.is_err());
+        assert!(present.is_ok());
+    }
+
+    #[test]
+    fn test_encode_into_after() {
+        let path = temp_path("after.png");
+        let bytes = Png::from_chunks_unchecked(vec![
+            chunk("IHDR", ""),
+            chunk("IDAT", ""),
+            chunk("IEND", ""),
+        ])
+        .as_bytes();
+        std::fs::write(&path, bytes).unwrap();
+
+        let ihdr = ChunkType::from_str("IHDR").unwrap();
+        encode_into(&path, &path, chunk("ruSt", ""), Some(&ihdr)).unwrap();
+        let missing = ChunkType::from_str("PLTE").unwrap();
+        let res = encode_into(&path, &path, chunk("ruSt", ""), Some(&missing));
+        let png = read_png(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(res, Err(PngMeError::ChunkNotFound(_))));
+        let types: Vec<String> = png
+            .into_iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "ruSt", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_write_atomic_keeps_original_on_error() {
+        let path = temp_path("atomic.png");
+        std::fs::write(&path, b"original").unwrap();
+
+        let res = write_atomic(&path, |file| {
+            file.write_all(b"partial")?;
+            Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"))
+        });
+        let after_error = std::fs::read(&path).unwrap();
+        write_atomic(&path, |file| file.write_all(b"replaced")).unwrap();
+        let after_success = std::fs::read(&path).unwrap();
+        let tmp_prefix = format!(
+            ".{}.{}.",
+            path.file_name().unwrap().to_string_lossy(),
+            std::process::id()
+        );
+        let leftover = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .any(|entry| {
+                let name = entry.unwrap().file_name();
+                name.to_string_lossy().starts_with(&tmp_prefix)
+            });
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(res.is_err());
+        assert_eq!(after_error, b"original");
+        assert_eq!(after_success, b"replaced");
+        assert!(!leftover);
+    }
+
+    #[test]
+    fn test_backup() {
+        let path = temp_path("backup.png");
+        let backup_path = temp_path("backup.png.bak");
+        std::fs::write(&path, b"original").unwrap();
+
+        backup(&path).unwrap();
+        std::fs::write(&path, b"edited").unwrap();
+        let second = backup(&path);
+        let backed_up = std::fs::read(&backup_path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&backup_path).unwrap();
+
+        assert_eq!(backed_up, b"original");
+        assert_eq!(second.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn test_write_png_keeps_trailer() {
+        let mut bytes = empty_png();
+        bytes.extend_from_slice(b"trailer");
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+
+        let path = temp_path("trailer.png");
+        write_png(&path, &png).unwrap();
+        let actual = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(actual, bytes);
+    }
+
+    #[test]
+    fn test_remove_critical_chunk_needs_force() {
+        let png = Png::from_chunks_unchecked(vec![chunk("IDAT", "pixels"), chunk("IEND", "")]);
+        let path = temp_path("remove-critical.png");
+        std::fs::write(&path, png.as_bytes()).unwrap();
+        let remove_idat = |force| {
+            remove(RemoveArgs {
+                file_path: path.clone(),
+                chunk_type: ChunkType::from_str("IDAT").unwrap(),
+                index: None,
+                all: false,
+                print: false,
+                force,
+                stdout: false,
+                dry_run: false,
+                backup: false,
+            })
+        };
+
+        assert!(remove_idat(false).is_err());
+        assert!(read_png(&path).unwrap().chunk_by_type("IDAT").is_some());
+        remove_idat(true).unwrap();
+        let png = read_png(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(png.chunk_by_type("IDAT").is_none());
+    }
+
+    #[test]
+    fn test_diff_chunks() {
+        let old = Png::from_chunks_unchecked(vec![
+            chunk("IHDR", "header"),
+            chunk("teXt", "comment"),
+            chunk("IDAT", "one"),
+            chunk("IDAT", "two"),
+            chunk("IEND", ""),
+        ]);
+        let new = Png::from_chunks_unchecked(vec![
+            chunk("IHDR", "header"),
+            chunk("IDAT", "one"),
+            chunk("IDAT", "three"),
+            chunk("ruSt", "secret"),
+            chunk("IEND", ""),
+        ]);
+
+        let diffs = diff_chunks(&old, &new);
+        assert_eq!(
+            diffs,
+            [
+                ChunkDiff::Removed(0, &old.chunks()[1]),
+                ChunkDiff::Changed(1, &old.chunks()[3], &new.chunks()[2]),
+                ChunkDiff::Added(0, &new.chunks()[3]),
+            ]
+        );
+        assert_eq!(diffs[2].to_string(), "+ ruSt #0 (6 bytes)");
+        assert!(diff_chunks(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_find_pngs() {
+        let dir = temp_path("scan");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        for name in ["b.png", "a.PNG", "notes.txt", "nested/c.png"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("nested/loop")).unwrap();
+
+        let mut flat = vec![];
+        find_pngs(&dir, false, &mut flat).unwrap();
+        let mut recursive = vec![];
+        find_pngs(&dir, true, &mut recursive).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(flat, [dir.join("a.PNG"), dir.join("b.png")]);
+        assert_eq!(
+            recursive,
+            [
+                dir.join("a.PNG"),
+                dir.join("b.png"),
+                dir.join("nested/c.png")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chunk_filter() {
+        let chunk_type = |s| ChunkType::from_str(s).unwrap();
+        assert!(shown(None, &chunk_type("IDAT")));
+        assert!(!shown(Some(ChunkFilter::Private), &chunk_type("RUST")));
+        assert!(shown(Some(ChunkFilter::Private), &chunk_type("RuSt")));
+        assert!(!shown(Some(ChunkFilter::Ancillary), &chunk_type("IDAT")));
+        assert!(shown(Some(ChunkFilter::Ancillary), &chunk_type("tEXt")));
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview(b"hello"), "\"hello\"");
+        assert_eq!(preview(&[b'a'; 50]), format!("\"{}\"...", "a".repeat(40)));
+        assert_eq!(preview(&[0xff, 0xfe]), "(binary)");
+    }
+
+    #[test]
+    fn test_extract_binary_data() {
+        let bytes: Vec<u8> = (0..=255).rev().collect();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let png_path = temp_path("extract.png");
+        let output = temp_path("extract.bin");
+        let png = Png::from_chunks_unchecked(vec![Chunk::new(chunk_type, bytes.clone()).unwrap()]);
+        std::fs::write(&png_path, png.as_bytes()).unwrap();
+
+        extract(ExtractArgs {
+            file_path: png_path.clone(),
+            chunk_type,
+            output_file: output.clone(),
+        })
+        .unwrap();
+        let missing = extract(ExtractArgs {
+            file_path: png_path.clone(),
+            chunk_type: ChunkType::from_str("teXt").unwrap(),
+            output_file: output.clone(),
+        });
+        let actual = std::fs::read(&output).unwrap();
+        std::fs::remove_file(&png_path).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        assert_eq!(actual, bytes);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_graft() {
+        let iend = chunk("IEND", "");
+        let chunk = chunk("ruSt", "metadata");
+        let source = temp_path("graft-source.png");
+        let destination = temp_path("graft-destination.png");
+        let png = Png::from_chunks_unchecked(vec![chunk.clone(), iend.clone()]);
+        std::fs::write(&source, png.as_bytes()).unwrap();
+        std::fs::write(&destination, empty_png()).unwrap();
+
+        let graft_type = |chunk_type| {
+            graft(GraftArgs {
+                source: source.clone(),
+                destination: destination.clone(),
+                chunk_type: ChunkType::from_str(chunk_type).unwrap(),
+            })
+        };
+        graft_type("ruSt").unwrap();
+        let missing = graft_type("teXt");
+        let png = read_png(&destination).unwrap();
+        std::fs::remove_file(&source).unwrap();
+        std::fs::remove_file(&destination).unwrap();
+
+        assert!(missing.is_err());
+        assert_eq!(png.chunks()[0], chunk);
+    }
+
+    #[test]
+    fn test_check_message_len() {
+        assert!(check_message_len(0, None).is_ok());
+        assert!(check_message_len(Chunk::MAX_LENGTH as usize, None).is_ok());
+        assert!(check_message_len(Chunk::MAX_LENGTH as usize + 1, None).is_err());
+        assert!(check_message_len(u32::MAX as usize + 1, None).is_err());
+        assert!(check_message_len(100, Some(100)).is_ok());
+        assert!(check_message_len(101, Some(100)).is_err());
+    }
+
+    #[test]
+    fn test_encode_base64_message() {
+        let png_path = temp_path("base64.png");
+        std::fs::write(&png_path, empty_png()).unwrap();
+        let encode_message = |message: &str| {
+            encode(EncodeArgs {
+                file_path: png_path.clone(),
+                chunk_type: Some(ChunkType::from_str("ruSt").unwrap()),
+                message: Some(message.to_string()),
+                output_file: None,
+                message_file: None,
+                stdout: false,
+                password: None,
+                compress: false,
+                base64: true,
+                max_message_len: None,
+                after: None,
+                backup: false,
+                verify: true,
+                in_place: true,
+                output_dir: None,
+                random_type: false,
+            })
+        };
+
+        let invalid = encode_message("not base64!");
+        encode_message("AP9/gA==").unwrap();
+        let png = read_png(&png_path).unwrap();
+        std::fs::remove_file(&png_path).unwrap();
+
+        assert!(invalid.is_err());
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data(),
+            [0x00, 0xff, 0x7f, 0x80]
+        );
+    }
+
+    #[test]
+    fn test_batch_encode() {
+        let bytes = empty_png();
+        let files = vec![
+            temp_path("batch-1.png"),
+            temp_path("batch-missing.png"),
+            temp_path("batch-2.png"),
+        ];
+        std::fs::write(&files[0], &bytes).unwrap();
+        std::fs::write(&files[2], &bytes).unwrap();
+
+        let res = batch_encode(BatchEncodeArgs {
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            message: "watermark".to_string(),
+            files: files.clone(),
+            out_dir: None,
+            jobs: None,
+        });
+        let first = read_png(&files[0]).unwrap();
+        let second = read_png(&files[2]).unwrap();
+        std::fs::remove_file(&files[0]).unwrap();
+        std::fs::remove_file(&files[2]).unwrap();
+
+        assert!(res.is_err());
+        for png in [first, second] {
+            assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"watermark");
+        }
+    }
+
+    #[test]
+    fn test_batch_encode_duplicate_outputs() {
+        let dir = temp_path("batch-duplicates");
+        let out_dir = dir.join("out");
+        let bytes = empty_png();
+        let files = vec![dir.join("a").join("x.png"), dir.join("b").join("x.png")];
+        for path in &files {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, &bytes).unwrap();
+        }
+
+        let res = batch_encode(BatchEncodeArgs {
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            message: "watermark".to_string(),
+            files,
+            out_dir: Some(out_dir.clone()),
+            jobs: None,
+        });
+        let written = out_dir.join("x.png").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(res.is_err());
+        assert!(!written);
+    }
+
+    #[test]
+    fn test_batch_encode_parallel() {
+        let dir = temp_path("batch-parallel");
+        let out_dir = dir.join("out");
+        std::fs::create_dir_all(&dir).unwrap();
+        let bytes = Png::from_chunks_unchecked(vec![
+            chunk("IDAT", vec![0x5a; 256 * 1024]),
+            chunk("IEND", ""),
+        ])
+        .as_bytes();
+        let files: Vec<PathBuf> = (0..32)
+            .map(|i| {
+                let path = dir.join(format!("{i}.png"));
+                std::fs::write(&path, &bytes).unwrap();
+                path
+            })
+            .collect();
+
+        batch_encode(BatchEncodeArgs {
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            message: "watermark".to_string(),
+            files: files.clone(),
+            out_dir: Some(out_dir.clone()),
+            jobs: Some(4),
+        })
+        .unwrap();
+
+        for path in &files {
+            let png = read_png(&out_dir.join(path.file_name().unwrap())).unwrap();
+            assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"watermark");
+            assert_eq!(std::fs::read(path).unwrap(), bytes);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_set_text() {
+        let png_path = temp_path("set-text.png");
+        std::fs::write(&png_path, empty_png()).unwrap();
+
+        set_text(SetTextArgs {
+            file_path: png_path.clone(),
+            keyword: "Author".to_string(),
+            value: "Ferris".to_string(),
+        })
+        .unwrap();
+        let png = read_png(&png_path).unwrap();
+        std::fs::remove_file(&png_path).unwrap();
+
+        assert_eq!(png.chunks()[0].chunk_type().to_string(), "tEXt");
+        assert_eq!(png.chunks()[0].data(), b"Author\0Ferris");
+        assert_eq!(png.chunks()[1].chunk_type().to_string(), "IEND");
+    }
+}
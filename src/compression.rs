@@ -0,0 +1,89 @@
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::{self, Read, Write};
+
+/// Marks chunk data written by `compress`, so it can be told apart from a
+/// message that was stored as-is.
+const MAGIC: &[u8] = b"\0ZLIB";
+
+/// Most bytes `decompress` and `inflate` produce, so a small compressed
+/// chunk from an untrusted file can't expand to fill up memory.
+pub const MAX_INFLATED_LEN: usize = 64 * 1024 * 1024;
+
+pub fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(MAGIC.to_vec(), Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let compressed = data
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "data is not compressed"))?;
+    inflate(compressed)
+}
+
+/// Decompresses a plain zlib stream, as used by the PNG format itself.
+/// Fails if it holds more than `MAX_INFLATED_LEN` bytes.
+pub fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    inflate_limited(data, MAX_INFLATED_LEN)
+}
+
+/// Like `inflate`, but fails once the output grows past `limit` bytes.
+pub fn inflate_limited(data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    let mut res = Vec::new();
+    ZlibDecoder::new(data)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut res)?;
+    if res.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("compressed data expands to more than {limit} bytes"),
+        ));
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_round_trip() {
+        let message = "all work and no play makes jack a dull boy\n".repeat(100);
+        let compressed = compress(message.as_bytes()).unwrap();
+        assert!(is_compressed(&compressed));
+        assert_eq!(decompress(&compressed).unwrap(), message.as_bytes());
+
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let plain = Chunk::new(chunk_type, message.into_bytes()).unwrap();
+        let packed = Chunk::new(chunk_type, compressed).unwrap();
+        assert!(packed.length() < plain.length());
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let bomb = compress(&vec![0; 1024 * 1024]).unwrap();
+        let stream = &bomb[MAGIC.len()..];
+        assert!(bomb.len() < 4096);
+        assert!(inflate_limited(stream, 1024).is_err());
+        assert_eq!(
+            inflate_limited(stream, 1024 * 1024).unwrap().len(),
+            1024 * 1024
+        );
+    }
+
+    #[test]
+    fn test_plain_data_is_not_compressed() {
+        assert!(!is_compressed(b"hello"));
+        assert!(decompress(b"hello").is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use thiserror::Error;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("failed to derive key from password: {0}")]
+    KeyDerivation(argon2::Error),
+    #[error("encrypted message is too short")]
+    TooShort,
+    #[error("wrong password or corrupted message")]
+    Authentication,
+}
+
+/// Encrypts `message` with a key derived from `password`.
+///
+/// The result is laid out as `salt || nonce || ciphertext`, so it can be
+/// stored as chunk data and handed back to `decrypt` as-is.
+pub fn encrypt(message: &[u8], password: &str) -> Result<Vec<u8>, CryptoError> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = cipher(password, &salt)?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, message)
+        .map_err(|_| CryptoError::Authentication)?;
+    Ok([&salt[..], &nonce[..], &ciphertext[..]].concat())
+}
+
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>, CryptoError> {
+    if data.len() < SALT_LEN + NONCE_LEN {
+        return Err(CryptoError::TooShort);
+    }
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    cipher(password, salt)?
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::Authentication)
+}
+
+fn cipher(password: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, CryptoError> {
+    let mut key = [0; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(CryptoError::KeyDerivation)?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data = encrypt(b"top secret", "hunter2").unwrap();
+        assert_eq!(decrypt(&data, "hunter2").unwrap(), b"top secret");
+    }
+
+    #[test]
+    fn test_ciphertext_hides_message() {
+        let data = encrypt(b"top secret", "hunter2").unwrap();
+        assert!(!data.windows(10).any(|w| w == b"top secret"));
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let data = encrypt(b"top secret", "hunter2").unwrap();
+        assert!(matches!(
+            decrypt(&data, "hunter3"),
+            Err(CryptoError::Authentication)
+        ));
+    }
+
+    #[test]
+    fn test_too_short() {
+        assert!(matches!(
+            decrypt(&[0; 10], "hunter2"),
+            Err(CryptoError::TooShort)
+        ));
+    }
+}
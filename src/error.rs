@@ -0,0 +1,53 @@
+use std::io;
+use std::string::FromUtf8Error;
+use thiserror::Error;
+
+use crate::chunk::ChunkError;
+use crate::chunk_type::ChunkTypeError;
+#[cfg(feature = "crypto")]
+use crate::crypto::CryptoError;
+use crate::ihdr::IhdrError;
+use crate::png::Violation;
+
+/// Error returned by the library functions, so callers can tell failure
+/// kinds apart.
+#[derive(Debug, Error)]
+pub enum PngMeError {
+    #[error("not a PNG: bad signature")]
+    BadSignature,
+    #[error("not a PNG: input is {0} bytes, shorter than the 8-byte signature")]
+    TooShort(usize),
+    #[error("CRC mismatch: expected {expected:08x}, found {actual:08x}")]
+    CrcMismatch { expected: u32, actual: u32 },
+    #[error("chunk type {0} is not found")]
+    ChunkNotFound(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    InvalidChunkType(#[from] ChunkTypeError),
+    /// A chunk that is malformed in any other way, e.g. truncated.
+    #[error(transparent)]
+    InvalidChunk(ChunkError),
+    #[error("{0} bytes of trailing data after IEND")]
+    TrailingData(usize),
+    #[error("chunk data is not valid UTF-8")]
+    InvalidUtf8(#[from] FromUtf8Error),
+    #[cfg(feature = "crypto")]
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[error("invalid IHDR: {0}")]
+    InvalidIhdr(#[from] IhdrError),
+    #[error("invalid PNG structure: {0}")]
+    InvalidStructure(#[from] Violation),
+}
+
+impl From<ChunkError> for PngMeError {
+    fn from(e: ChunkError) -> Self {
+        match e {
+            ChunkError::InvalidCRC { expected, actual } => Self::CrcMismatch { expected, actual },
+            ChunkError::InvalidChunkType(e) => Self::InvalidChunkType(e),
+            ChunkError::InvalidBytes(e) => Self::Io(e),
+            e => Self::InvalidChunk(e),
+        }
+    }
+}
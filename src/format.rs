@@ -0,0 +1,82 @@
+use std::fmt::{self, Display};
+
+/// Canonical hex+ASCII dump of a byte slice, laid out like `hexdump -C`.
+pub struct HexDump<'a>(pub &'a [u8]);
+
+impl Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.0.chunks(16).enumerate() {
+            write!(f, "{:08x} ", i * 16)?;
+            for j in 0..16 {
+                if j % 8 == 0 {
+                    write!(f, " ")?;
+                }
+                match line.get(j) {
+                    Some(byte) => write!(f, "{byte:02x} ")?,
+                    None => write!(f, "   ")?,
+                }
+            }
+            write!(f, " |")?;
+            for &byte in line {
+                let c = if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                };
+                write!(f, "{c}")?;
+            }
+            writeln!(f, "|")?;
+        }
+        writeln!(f, "{:08x}", self.0.len())
+    }
+}
+
+pub fn hexdump(data: &[u8]) -> String {
+    HexDump(data).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump() {
+        let expected = "\
+00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  |Hello, world!...|
+00000010  41                                                |A|
+00000011
+";
+        assert_eq!(hexdump(b"Hello, world!\n\x00\xffA"), expected);
+    }
+
+    #[test]
+    fn test_hexdump_full_rows() {
+        let printable = "\
+00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|
+00000010
+";
+        assert_eq!(hexdump(b"0123456789abcdef"), printable);
+
+        let binary = "\
+00000000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  |................|
+00000010  80 81 82 83 84 85 86 87  88 89 8a 8b 8c 8d 8e 8f  |................|
+00000020
+";
+        let data: Vec<u8> = (0x00..0x10).chain(0x80..0x90).collect();
+        assert_eq!(hexdump(&data), binary);
+    }
+
+    #[test]
+    fn test_hexdump_partial_line() {
+        let expected = "\
+00000000  61 62 63 64 65 66 67 68  69                       |abcdefghi|
+00000009
+";
+        assert_eq!(hexdump(b"abcdefghi"), expected);
+    }
+
+    #[test]
+    fn test_hexdump_empty() {
+        assert_eq!(hexdump(&[]), "00000000\n");
+    }
+}
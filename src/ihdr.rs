@@ -0,0 +1,126 @@
+use std::fmt::{self, Display};
+use thiserror::Error;
+
+/// Contents of the `IHDR` chunk, which describes the image itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlaced: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+#[derive(Debug, Error)]
+pub enum IhdrError {
+    #[error("IHDR must be 13 bytes long, got {0}")]
+    WrongLength(usize),
+    #[error("unknown color type {0}")]
+    InvalidColorType(u8),
+    #[error("unknown interlace method {0}")]
+    InvalidInterlaceMethod(u8),
+}
+
+impl Ihdr {
+    pub const LENGTH: usize = 13;
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = IhdrError;
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let data: &[u8; Ihdr::LENGTH] = data
+            .try_into()
+            .map_err(|_| IhdrError::WrongLength(data.len()))?;
+        let color_type = ColorType::try_from(data[9])?;
+        let interlaced = match data[12] {
+            0 => false,
+            1 => true,
+            method => return Err(IhdrError::InvalidInterlaceMethod(method)),
+        };
+        Ok(Self {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type,
+            compression_method: data[10],
+            filter_method: data[11],
+            interlaced,
+        })
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = IhdrError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Grayscale),
+            2 => Ok(Self::Rgb),
+            3 => Ok(Self::Indexed),
+            4 => Ok(Self::GrayscaleAlpha),
+            6 => Ok(Self::Rgba),
+            _ => Err(IhdrError::InvalidColorType(value)),
+        }
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Grayscale => "grayscale",
+            Self::Rgb => "RGB",
+            Self::Indexed => "indexed color",
+            Self::GrayscaleAlpha => "grayscale with alpha",
+            Self::Rgba => "RGB with alpha",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IHDR: [u8; 13] = [0, 0, 2, 128, 0, 0, 1, 224, 8, 6, 0, 0, 1];
+
+    #[test]
+    fn test_ihdr_from_bytes() {
+        let ihdr = Ihdr::try_from(&IHDR[..]).unwrap();
+        assert_eq!(ihdr.width, 640);
+        assert_eq!(ihdr.height, 480);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert!(ihdr.interlaced);
+        assert_eq!(ihdr.color_type.to_string(), "RGB with alpha");
+    }
+
+    #[test]
+    fn test_invalid_ihdr() {
+        assert!(matches!(
+            Ihdr::try_from(&IHDR[..12]),
+            Err(IhdrError::WrongLength(12))
+        ));
+        let mut data = IHDR;
+        data[9] = 5;
+        assert!(matches!(
+            Ihdr::try_from(&data[..]),
+            Err(IhdrError::InvalidColorType(5))
+        ));
+        let mut data = IHDR;
+        data[12] = 2;
+        assert!(matches!(
+            Ihdr::try_from(&data[..]),
+            Err(IhdrError::InvalidInterlaceMethod(2))
+        ));
+    }
+}
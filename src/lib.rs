@@ -0,0 +1,38 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "cli")]
+pub mod args;
+pub mod chunk;
+pub mod chunk_type;
+#[cfg(feature = "cli")]
+pub mod commands;
+#[cfg(feature = "compression")]
+pub mod compression;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod format;
+#[cfg(feature = "std")]
+pub mod ihdr;
+#[cfg(feature = "std")]
+pub mod message;
+#[cfg(feature = "std")]
+pub mod png;
+#[cfg(feature = "std")]
+pub mod progress;
+#[cfg(feature = "compression")]
+pub mod text;
+
+#[cfg(feature = "std")]
+pub use error::PngMeError;
+#[cfg(feature = "std")]
+pub use message::{decode_message, encode_message, message_chunks, MessageLookup};
+
+#[cfg(feature = "std")]
+pub type Error = PngMeError;
+#[cfg(feature = "std")]
+pub type Result<T> = std::result::Result<T, Error>;
@@ -1,22 +1,43 @@
-use args::PngMeArgs;
 use clap::Parser;
-use commands::{decode, encode, print, remove, PngMeCommmands};
+use pngme::args::PngMeArgs;
+use pngme::commands::{
+    batch_encode, completions, count, decode, diff, encode, extract, fix_crc, graft, info, modify,
+    print, remove, scan, set_color, set_quiet, set_text, text, trailer, validate, verify,
+    PngMeCommmands, ERROR_STYLE,
+};
+use std::error::Error;
+use std::process;
 
-mod args;
-mod chunk;
-mod chunk_type;
-mod commands;
-mod png;
-
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
-
-fn main() -> Result<()> {
+fn main() {
     let cli = PngMeCommmands::parse();
-    match cli.action {
+    set_quiet(cli.quiet);
+    set_color(cli.color);
+    if let Err(e) = run(cli.action) {
+        anstream::eprintln!("{ERROR_STYLE}error:{ERROR_STYLE:#} {e}");
+        process::exit(1);
+    }
+}
+
+fn run(action: PngMeArgs) -> Result<(), Box<dyn Error>> {
+    match action {
         PngMeArgs::Encode(args) => encode(args),
         PngMeArgs::Decode(args) => decode(args),
         PngMeArgs::Remove(args) => remove(args),
         PngMeArgs::Print(args) => print(args),
+        PngMeArgs::Modify(args) => modify(args),
+        PngMeArgs::FixCrc(args) => fix_crc(args),
+        PngMeArgs::Count(args) => count(args),
+        PngMeArgs::Trailer(args) => trailer(args),
+        PngMeArgs::Validate(args) => validate(args),
+        PngMeArgs::Verify(args) => verify(args),
+        PngMeArgs::BatchEncode(args) => batch_encode(args),
+        PngMeArgs::Extract(args) => extract(args),
+        PngMeArgs::Graft(args) => graft(args),
+        PngMeArgs::Diff(args) => diff(args),
+        PngMeArgs::Scan(args) => scan(args),
+        PngMeArgs::Info(args) => info(args),
+        PngMeArgs::Text(args) => text(args),
+        PngMeArgs::SetText(args) => set_text(args),
+        PngMeArgs::Completions(args) => completions(args),
     }
 }
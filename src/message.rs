@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::Result;
+
+/// Stores `message` in a new chunk of type `chunk_type`, placed before
+/// IEND. Unlike the `encode` command, this does no IO, compression or
+/// encryption.
+pub fn encode_message(png: &mut Png, chunk_type: ChunkType, message: &[u8]) -> Result<()> {
+    png.append_chunk(Chunk::new(chunk_type, message.to_vec())?);
+    Ok(())
+}
+
+/// How `message_chunks` picks out the chunks holding messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageLookup<'a> {
+    /// The chunk at this (zero-based) position, whatever its type
+    Index(usize),
+    /// Chunks of exactly this type
+    Type(&'a str),
+    /// Chunks whose type matches this one ignoring ASCII case
+    TypeIgnoringCase(&'a str),
+}
+
+/// The chunks `lookup` matches. Without `all`, only the first match is
+/// returned, or with `TypeIgnoringCase` the first of each casing: the case
+/// of each letter is a property bit, so those are distinct chunk types.
+pub fn message_chunks<'a>(png: &'a Png, lookup: MessageLookup, all: bool) -> Vec<&'a Chunk> {
+    match lookup {
+        MessageLookup::Index(i) => png.chunk_at(i).into_iter().collect(),
+        MessageLookup::Type(chunk_type) if all => png.chunks_by_type(chunk_type),
+        MessageLookup::Type(chunk_type) => png.chunk_by_type(chunk_type).into_iter().collect(),
+        MessageLookup::TypeIgnoringCase(chunk_type) => {
+            let matching = png.filter(|c| c.type_str().eq_ignore_ascii_case(chunk_type));
+            if all {
+                return matching;
+            }
+            let mut casings = HashSet::new();
+            matching
+                .into_iter()
+                .filter(|c| casings.insert(*c.chunk_type()))
+                .collect()
+        }
+    }
+}
+
+/// The data of the first chunk of type `chunk_type`, as stored: no
+/// decompression or decryption is done.
+pub fn decode_message(png: &Png, chunk_type: &str) -> Option<Vec<u8>> {
+    message_chunks(png, MessageLookup::Type(chunk_type), false)
+        .first()
+        .map(|chunk| chunk.data().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_encode_message() {
+        let iend = Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]).unwrap();
+        let mut png = Png::from_chunks_unchecked(vec![iend]);
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+
+        encode_message(&mut png, chunk_type, b"hidden").unwrap();
+
+        assert_eq!(png.chunks()[0].chunk_type(), &chunk_type);
+        assert_eq!(png.chunks()[0].data(), b"hidden");
+        assert_eq!(png.chunks()[1].type_str(), "IEND");
+    }
+
+    #[test]
+    fn test_decode_message() {
+        let iend = Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]).unwrap();
+        let mut png = Png::from_chunks_unchecked(vec![iend]);
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        encode_message(&mut png, chunk_type, b"first").unwrap();
+        encode_message(&mut png, chunk_type, b"second").unwrap();
+
+        assert_eq!(decode_message(&png, "ruSt").unwrap(), b"first");
+        assert_eq!(decode_message(&png, "teSt"), None);
+    }
+
+    #[test]
+    fn test_message_chunks() {
+        let chunk = |chunk_type, data: &str| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.into()).unwrap()
+        };
+        let png = Png::from_chunks_unchecked(vec![
+            chunk("ruSt", "one"),
+            chunk("RuSt", "two"),
+            chunk("ruSt", "three"),
+            chunk("teXt", "four"),
+        ]);
+        let data = |lookup, all| -> Vec<String> {
+            message_chunks(&png, lookup, all)
+                .iter()
+                .map(|c| c.data_as_string().unwrap())
+                .collect()
+        };
+
+        assert_eq!(data(MessageLookup::Type("ruSt"), false), ["one"]);
+        assert_eq!(data(MessageLookup::Type("ruSt"), true), ["one", "three"]);
+        assert_eq!(data(MessageLookup::Index(3), false), ["four"]);
+        assert!(data(MessageLookup::Index(4), false).is_empty());
+        assert_eq!(
+            data(MessageLookup::TypeIgnoringCase("RUST"), false),
+            ["one", "two"]
+        );
+        assert_eq!(
+            data(MessageLookup::TypeIgnoringCase("rust"), true),
+            ["one", "two", "three"]
+        );
+        assert!(data(MessageLookup::TypeIgnoringCase("idat"), true).is_empty());
+    }
+}
@@ -1,45 +1,174 @@
 use std::{
+    collections::HashMap,
     fmt::Display,
-    io::{BufReader, Read},
+    io::{self, BufRead, ErrorKind, Read},
 };
 use thiserror::Error;
 
-use crate::{chunk::Chunk, chunk_type::ChunkTypeError, Result};
+use crate::{
+    chunk::{Chunk, ChunkError, ChunkIter, ParseOptions, CRC},
+    chunk_type::ChunkType,
+    error::PngMeError,
+    ihdr::Ihdr,
+    Result,
+};
 
 pub struct Png {
     header: [u8; 8],
     chunks: Vec<Chunk>,
+    trailer: Vec<u8>,
+}
+
+/// A way in which a PNG breaks the structural rules checked by
+/// `Png::validate`.
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum Violation {
+    #[error("the PNG has no chunks")]
+    NoChunks,
+    #[error("chunk {index} is {found}, but the first chunk must be IHDR")]
+    FirstNotIhdr { index: usize, found: ChunkType },
+    #[error("chunk {index} is {found}, but the last chunk must be IEND")]
+    LastNotIend { index: usize, found: ChunkType },
+    #[error("chunk {index} is another {chunk_type}, which must appear only once")]
+    Duplicate { index: usize, chunk_type: ChunkType },
+    #[error("chunk {index} is an IDAT separated from the previous IDAT")]
+    IdatNotContiguous { index: usize },
+    #[error("chunk {index} ({chunk_type}) has an invalid CRC")]
+    InvalidCrc { index: usize, chunk_type: ChunkType },
+}
+
+/// Result of `Png::verify_crcs`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CrcReport {
+    /// Number of chunks whose CRC matched.
+    pub chunks: usize,
+    /// The first chunk whose CRC didn't match; checking stops there.
+    pub mismatch: Option<CrcMismatch>,
+    /// Bytes after `IEND` that don't form chunks, so have no CRC to check.
+    pub trailing: u64,
 }
 
-#[derive(Debug, Error)]
-pub enum PngError {
-    #[error("invalid header")]
-    InvalidHeader,
-    #[error("invalid chunk type")]
-    InvalidChunkType(#[from] ChunkTypeError),
-    #[error("chunk is not found")]
-    ChunkNotFound,
+#[derive(Debug, PartialEq, Eq)]
+pub struct CrcMismatch {
+    pub index: usize,
+    /// Byte offset of the chunk's length field in the file.
+    pub offset: u64,
+    pub chunk_type: ChunkType,
+    pub expected: u32,
+    pub actual: u32,
 }
 
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
-    pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
+    /// Builds a PNG from `chunks`, which must start with IHDR and end with
+    /// IEND. Use `validate` for the remaining structural checks.
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Result<Png> {
+        let png = Self::from_chunks_unchecked(chunks);
+        let violation = png.validate().into_iter().find(|v| {
+            matches!(
+                v,
+                Violation::NoChunks
+                    | Violation::FirstNotIhdr { .. }
+                    | Violation::LastNotIend { .. }
+            )
+        });
+        match violation {
+            Some(violation) => Err(violation.into()),
+            None => Ok(png),
+        }
+    }
+    /// Like `from_chunks`, but accepts the chunks in any order.
+    pub fn from_chunks_unchecked(chunks: Vec<Chunk>) -> Png {
         Self {
             header: Self::STANDARD_HEADER,
             chunks,
+            trailer: vec![],
         }
     }
+    /// Adds `chunk` as the last chunk before `IEND`, or at the end if there
+    /// is no `IEND`.
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+        let i = self.append_position();
+        self.chunks.insert(i, chunk);
+    }
+    /// Like `append_chunk` for each of `chunks` in order, but moves the
+    /// chunks after them only once.
+    pub fn append_chunks(&mut self, chunks: Vec<Chunk>) {
+        let i = self.append_position();
+        self.chunks.splice(i..i, chunks);
+    }
+    /// Index of the last `IEND`, or the end if there is none.
+    fn append_position(&self) -> usize {
+        self.chunks
+            .iter()
+            .rposition(|c| c.chunk_type().bytes() == *b"IEND")
+            .unwrap_or(self.chunks.len())
+    }
+    /// Inserts `chunk` right before the first chunk of type `before_type`.
+    pub fn insert_chunk_before(&mut self, before_type: &str, chunk: Chunk) -> Result<()> {
+        let i = self.position(before_type)?;
+        self.chunks.insert(i, chunk);
+        Ok(())
+    }
+    /// Inserts `chunk` right after the first chunk of type `after_type`.
+    pub fn insert_chunk_after(&mut self, after_type: &str, chunk: Chunk) -> Result<()> {
+        let i = self.position(after_type)?;
+        self.chunks.insert(i + 1, chunk);
+        Ok(())
+    }
+    fn position(&self, chunk_type: &str) -> Result<usize> {
+        self.chunks
+            .iter()
+            .position(|c| c.has_type(chunk_type))
+            .ok_or_else(|| PngMeError::ChunkNotFound(chunk_type.to_string()))
     }
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
-        for (i, chunk) in self.chunks().iter().enumerate() {
-            if chunk.chunk_type().to_string() == chunk_type {
-                return Ok(self.chunks.remove(i));
-            }
+        self.remove_chunk_at(chunk_type, 0)
+    }
+    /// Removes the `n`th (zero-based) chunk of the given type.
+    pub fn remove_chunk_at(&mut self, chunk_type: &str, n: usize) -> Result<Chunk> {
+        let position = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.has_type(chunk_type))
+            .nth(n)
+            .map(|(i, _)| i);
+        match position {
+            Some(i) => Ok(self.chunks.remove(i)),
+            None => Err(PngMeError::ChunkNotFound(chunk_type.to_string())),
+        }
+    }
+    /// Number of chunks of the given type.
+    pub fn count_chunks(&self, chunk_type: &str) -> usize {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.has_type(chunk_type))
+            .count()
+    }
+    /// Removes every chunk of the given type and returns how many there were.
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> usize {
+        let before = self.chunks.len();
+        self.chunks.retain(|chunk| !chunk.has_type(chunk_type));
+        before - self.chunks.len()
+    }
+    /// Swaps the first chunk of the given type for `chunk` in place and
+    /// returns the old one. Unlike removing and appending, the chunk keeps
+    /// its position.
+    pub fn replace_chunk(&mut self, chunk_type: &str, chunk: Chunk) -> Result<Chunk> {
+        match self.chunks.iter_mut().find(|c| c.has_type(chunk_type)) {
+            Some(old) => Ok(std::mem::replace(old, chunk)),
+            None => Err(PngMeError::ChunkNotFound(chunk_type.to_string())),
         }
-        Err(Box::new(PngError::ChunkNotFound))
+    }
+    /// Recomputes every chunk's CRC and returns how many were wrong.
+    pub fn fix_crcs(&mut self) -> usize {
+        self.chunks
+            .iter_mut()
+            .map(|chunk| chunk.fix_crc())
+            .filter(|&changed| changed)
+            .count()
     }
     pub fn header(&self) -> &[u8; 8] {
         &self.header
@@ -47,52 +176,423 @@ impl Png {
     pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
+    /// Chunks for editing in place, which keeps their order unlike removing
+    /// and re-appending them.
+    pub fn chunks_mut(&mut self) -> &mut [Chunk] {
+        &mut self.chunks
+    }
+    /// The chunk at position `i` in file order.
+    pub fn chunk_at(&self, i: usize) -> Option<&Chunk> {
+        self.chunks.get(i)
+    }
+    /// Byte offset of each chunk in the bytes `as_bytes` returns: 8 for
+    /// the first chunk, right after the signature, and then each chunk's
+    /// length, type and CRC fields plus its data further.
+    pub fn chunk_offsets(&self) -> Vec<usize> {
+        let mut offset = self.header.len();
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                let start = offset;
+                offset += Chunk::MIN_SIZE + chunk.data_len();
+                start
+            })
+            .collect()
+    }
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+        self.find(|chunk| chunk.has_type(chunk_type))
+    }
+    /// First chunk matching `predicate`.
+    pub fn find<F: Fn(&Chunk) -> bool>(&self, predicate: F) -> Option<&Chunk> {
+        self.chunks.iter().find(|chunk| predicate(chunk))
+    }
+    /// All chunks matching `predicate`, in file order.
+    pub fn filter<F: Fn(&Chunk) -> bool>(&self, predicate: F) -> Vec<&Chunk> {
         self.chunks
             .iter()
-            .find(|chunk| chunk.chunk_type().to_string() == chunk_type)
+            .filter(|chunk| predicate(chunk))
+            .collect()
     }
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let mut header: Vec<u8> = self.header().iter().copied().collect();
-        let mut chunks: Vec<u8> = self
-            .chunks()
+    /// On-wire bytes (length, type, data and CRC) of the first chunk of the
+    /// given type.
+    pub fn serialized_chunk(&self, chunk_type: &str) -> Option<Vec<u8>> {
+        self.chunk_by_type(chunk_type).map(Chunk::as_bytes)
+    }
+    pub fn chunks_by_type(&self, chunk_type: &str) -> Vec<&Chunk> {
+        self.filter(|chunk| chunk.has_type(chunk_type))
+    }
+    /// Parses a PNG chunk by chunk, so the raw file never has to be held
+    /// in memory alongside the parsed chunks.
+    pub fn from_reader<R: BufRead>(reader: &mut R) -> Result<Png> {
+        Self::from_reader_lenient(reader, true)
+    }
+    /// Like `try_from`, but with `verify_crc == false` chunks with a wrong
+    /// CRC are kept instead of failing the whole parse. Use
+    /// `Chunk::verify_crc` to find them afterwards.
+    pub fn try_from_lenient(value: &[u8], verify_crc: bool) -> Result<Png> {
+        Self::try_from_with(
+            value,
+            ParseOptions {
+                verify_crc,
+                ..Default::default()
+            },
+        )
+    }
+    pub fn try_from_with(value: &[u8], options: ParseOptions) -> Result<Png> {
+        if value.len() < Self::STANDARD_HEADER.len() {
+            return Err(PngMeError::TooShort(value.len()));
+        }
+        let mut reader = value;
+        Self::from_reader_with(&mut reader, options)
+    }
+    pub fn from_reader_lenient<R: BufRead>(reader: &mut R, verify_crc: bool) -> Result<Png> {
+        Self::from_reader_with(
+            reader,
+            ParseOptions {
+                verify_crc,
+                ..Default::default()
+            },
+        )
+    }
+    pub fn from_reader_with<R: BufRead>(reader: &mut R, options: ParseOptions) -> Result<Png> {
+        let header = Self::read_signature(reader)?;
+        let mut chunks = vec![];
+        while !reader.fill_buf()?.is_empty() {
+            let chunk = Chunk::from_reader_with(reader, options)?;
+            let is_end = chunk.chunk_type().bytes() == *b"IEND";
+            chunks.push(chunk);
+            if is_end {
+                break;
+            }
+        }
+        // earlier versions of pngme appended chunks after IEND, so keep
+        // well-formed ones and only treat what follows them as trailing data
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest)?;
+        let mut remaining = rest.as_slice();
+        while !remaining.is_empty() {
+            let mut cursor = remaining;
+            let strict = ParseOptions {
+                strict_type: true,
+                ..Default::default()
+            };
+            match Chunk::from_reader_with(&mut cursor, strict) {
+                Ok(chunk) => chunks.push(chunk),
+                Err(_) => break,
+            }
+            remaining = cursor;
+        }
+        Ok(Self {
+            header,
+            chunks,
+            trailer: remaining.to_vec(),
+        })
+    }
+    /// Parses the file at `path` directly from a memory map of it.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or modified, by this or another
+    /// process, until the function returns. Otherwise reading the mapped
+    /// bytes is undefined behaviour; on most platforms it crashes the
+    /// process or returns garbage.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap<P: AsRef<std::path::Path>>(path: P) -> Result<Png> {
+        Self::open_mmap_lenient(path, true)
+    }
+    /// # Safety
+    ///
+    /// See `open_mmap`.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap_lenient<P: AsRef<std::path::Path>>(
+        path: P,
+        verify_crc: bool,
+    ) -> Result<Png> {
+        Self::open_mmap_with(
+            path,
+            ParseOptions {
+                verify_crc,
+                ..Default::default()
+            },
+        )
+    }
+    /// # Safety
+    ///
+    /// See `open_mmap`.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap_with<P: AsRef<std::path::Path>>(
+        path: P,
+        options: ParseOptions,
+    ) -> Result<Png> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the map lives only for the parse, and the caller
+        // guarantees the file stays unchanged meanwhile
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Self::try_from_with(&map, options)
+    }
+    /// Like `try_from`, but fails with `PngMeError::TrailingData` if there
+    /// are bytes after the last chunk.
+    pub fn try_from_strict(value: &[u8]) -> Result<Png> {
+        let png = Png::try_from(value)?;
+        match png.trailer.len() {
+            0 => Ok(png),
+            n => Err(PngMeError::TrailingData(n)),
+        }
+    }
+    /// Checks the signature and returns an iterator over the chunks that
+    /// follow it, without parsing any of them yet.
+    pub fn read_chunks<R: Read>(mut reader: R) -> Result<ChunkIter<R>> {
+        Self::read_signature(&mut reader)?;
+        Ok(ChunkIter::new(reader))
+    }
+    fn read_signature<R: Read>(reader: &mut R) -> Result<[u8; 8]> {
+        let mut header = [0; 8];
+        match reader.read_exact(&mut header) {
+            Ok(()) if header == Self::STANDARD_HEADER => Ok(header),
+            // input shorter than the signature can't be a PNG either
+            Ok(()) => Err(PngMeError::BadSignature),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err(PngMeError::BadSignature),
+            Err(e) => Err(e.into()),
+        }
+    }
+    /// Checks the CRC of every chunk while streaming through `reader`,
+    /// without holding any chunk's data in memory. Stops at the first
+    /// mismatch.
+    ///
+    /// Like parsing, this keeps going after `IEND`: chunks there are checked
+    /// too, and whatever doesn't form a chunk is counted as trailing data.
+    pub fn verify_crcs<R: BufRead>(reader: &mut R) -> Result<CrcReport> {
+        Self::read_signature(reader)?;
+        let mut report = CrcReport {
+            chunks: 0,
+            mismatch: None,
+            trailing: 0,
+        };
+        let mut offset = Self::STANDARD_HEADER.len() as u64;
+        let mut after_iend = false;
+        loop {
+            let mut header = [0; 8];
+            let read = read_up_to(reader, &mut header)?;
+            if read == 0 {
+                break;
+            }
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let chunk_type = ChunkType::from_raw([header[4], header[5], header[6], header[7]]);
+            // past IEND only well-formed chunks count, as when parsing
+            let is_chunk = read == header.len()
+                && length <= Chunk::MAX_LENGTH
+                && (!after_iend || chunk_type.is_valid());
+            if !is_chunk {
+                if after_iend {
+                    report.trailing = read as u64 + io::copy(reader, &mut io::sink())?;
+                    break;
+                }
+                return Err(match read {
+                    8 => ChunkError::LengthTooLarge(length),
+                    _ => ChunkError::TooShort(read),
+                }
+                .into());
+            }
+
+            let mut digest = CRC.digest();
+            digest.update(&chunk_type.bytes());
+            let mut hashed = 0;
+            while hashed < length as usize {
+                let available = reader.fill_buf()?;
+                if available.is_empty() {
+                    break;
+                }
+                let n = available.len().min(length as usize - hashed);
+                digest.update(&available[..n]);
+                reader.consume(n);
+                hashed += n;
+            }
+            let mut crc = [0; 4];
+            let crc_read = if hashed == length as usize {
+                read_up_to(reader, &mut crc)?
+            } else {
+                0
+            };
+            if crc_read < crc.len() {
+                if after_iend {
+                    report.trailing = (header.len() + hashed + crc_read) as u64;
+                    break;
+                }
+                let e = if hashed == length as usize {
+                    ChunkError::MissingCrc
+                } else {
+                    ChunkError::Truncated {
+                        length,
+                        available: hashed,
+                    }
+                };
+                return Err(e.into());
+            }
+
+            let expected = digest.finalize();
+            let actual = u32::from_be_bytes(crc);
+            if actual != expected {
+                report.mismatch = Some(CrcMismatch {
+                    index: report.chunks,
+                    offset,
+                    chunk_type,
+                    expected,
+                    actual,
+                });
+                break;
+            }
+            report.chunks += 1;
+            offset += Chunk::MIN_SIZE as u64 + u64::from(length);
+            after_iend |= chunk_type.bytes() == *b"IEND";
+        }
+        Ok(report)
+    }
+    /// Checks the chunk order and CRCs against the PNG specification and
+    /// returns every violation found.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = vec![];
+        match (self.chunks.first(), self.chunks.last()) {
+            (Some(first), Some(last)) => {
+                if first.chunk_type().bytes() != *b"IHDR" {
+                    violations.push(Violation::FirstNotIhdr {
+                        index: 0,
+                        found: *first.chunk_type(),
+                    });
+                }
+                if last.chunk_type().bytes() != *b"IEND" {
+                    violations.push(Violation::LastNotIend {
+                        index: self.chunks.len() - 1,
+                        found: *last.chunk_type(),
+                    });
+                }
+            }
+            _ => violations.push(Violation::NoChunks),
+        }
+        let (mut ihdr_seen, mut iend_seen) = (false, false);
+        let mut last_idat = None;
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let chunk_type = *chunk.chunk_type();
+            let seen = match &chunk_type.bytes() {
+                b"IHDR" => Some(&mut ihdr_seen),
+                b"IEND" => Some(&mut iend_seen),
+                _ => None,
+            };
+            if let Some(seen) = seen {
+                if *seen {
+                    violations.push(Violation::Duplicate { index, chunk_type });
+                }
+                *seen = true;
+            }
+            if chunk_type.bytes() == *b"IDAT" {
+                if matches!(last_idat, Some(last) if last + 1 != index) {
+                    violations.push(Violation::IdatNotContiguous { index });
+                }
+                last_idat = Some(index);
+            }
+            if !chunk.verify_crc() {
+                violations.push(Violation::InvalidCrc { index, chunk_type });
+            }
+        }
+        violations
+    }
+    /// Parses the first `IHDR` chunk.
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        let chunk = self
+            .chunk_by_type("IHDR")
+            .ok_or_else(|| PngMeError::ChunkNotFound("IHDR".to_string()))?;
+        Ok(Ihdr::try_from(chunk.data())?)
+    }
+    /// Number of chunks of each type in the PNG.
+    pub fn chunk_counts(&self) -> HashMap<ChunkType, usize> {
+        let mut counts = HashMap::new();
+        for chunk in self.chunks.iter() {
+            *counts.entry(*chunk.chunk_type()).or_insert(0) += 1;
+        }
+        counts
+    }
+    /// Bytes after the last chunk that weren't part of the PNG. They are
+    /// not included in `as_bytes()`.
+    pub fn trailing_bytes(&self) -> &[u8] {
+        &self.trailer
+    }
+    /// Length of `as_bytes()`, computed without serializing the chunks.
+    /// Counts the data each chunk holds, like `chunk_offsets`, since that
+    /// is what gets written even if the declared length differs.
+    pub fn total_size(&self) -> usize {
+        let chunks: usize = self
+            .chunks
             .iter()
-            .map(|c| c.as_bytes())
-            .flatten()
-            .collect();
-        header.append(&mut chunks);
-        header
+            .map(|c| Chunk::MIN_SIZE + c.data_len())
+            .sum();
+        self.header.len() + chunks
+    }
+    pub fn as_bytes(&self) -> Vec<u8> {
+        // allocate once; the output can be as large as the whole image
+        let mut bytes = Vec::with_capacity(self.total_size());
+        bytes.extend_from_slice(&self.header);
+        for chunk in self.chunks.iter() {
+            bytes.extend_from_slice(&chunk.length().to_be_bytes());
+            bytes.extend_from_slice(&chunk.chunk_type().bytes());
+            bytes.extend_from_slice(chunk.data());
+            bytes.extend_from_slice(&chunk.crc().to_be_bytes());
+        }
+        bytes
     }
 }
 
 impl TryFrom<&[u8]> for Png {
     type Error = crate::Error;
     fn try_from(value: &[u8]) -> Result<Self> {
-        let mut reader = BufReader::new(value);
-        let mut header = [0; 8];
-        reader.read_exact(&mut header)?;
-        if header != Png::STANDARD_HEADER {
-            return Err(Box::new(PngError::InvalidHeader));
-        }
-        let mut chunks = vec![];
-        let mut length = [0; 4];
-        while let Ok(_) = reader.read_exact(&mut length) {
-            let mut chunk_left = vec![0; u32::from_be_bytes(length) as usize + 8];
-            reader.read_exact(&mut chunk_left)?;
-            let chunk_bytes: Vec<u8> = length.iter().chain(chunk_left.iter()).copied().collect();
-            let chunk = Chunk::try_from(chunk_bytes.as_slice())?;
-            chunks.push(chunk);
+        Png::try_from_lenient(value, true)
+    }
+}
+
+impl<'a> IntoIterator for &'a Png {
+    type Item = &'a Chunk;
+    type IntoIter = std::slice::Iter<'a, Chunk>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.iter()
+    }
+}
+
+/// Fills as much of `buf` as `reader` has left and returns how much that
+/// was.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
         }
-        Ok(Self { header, chunks })
     }
+    Ok(filled)
 }
 
+/// One line for the signature, then one line per chunk with its index,
+/// type, length and CRC.
 impl Display for Png {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.chunks
-            .iter()
-            .map(|chunk| write!(f, "{}", chunk))
-            .all(|r| r.is_ok());
+        let signature = if self.header == Self::STANDARD_HEADER {
+            "valid"
+        } else {
+            "invalid"
+        };
+        writeln!(
+            f,
+            "PNG signature: {signature}, {} chunk(s)",
+            self.chunks.len()
+        )?;
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            writeln!(
+                f,
+                "{i:>4}  {}  {:>10}  {:08x}",
+                chunk.chunk_type(),
+                chunk.length(),
+                chunk.crc()
+            )?;
+        }
         Ok(())
     }
 }
@@ -103,20 +603,19 @@ mod tests {
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
     use std::convert::TryFrom;
+    use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
         let chunks = testing_chunks();
-        Png::from_chunks(chunks)
+        Png::from_chunks_unchecked(chunks)
     }
 
     fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
@@ -124,17 +623,17 @@ mod tests {
 
         let chunk_type = match ChunkType::from_str(chunk_type) {
             Ok(chunk_type) => chunk_type,
-            Err(e) => return Err(Box::new(PngError::InvalidChunkType(e))),
+            Err(e) => return Err(PngMeError::InvalidChunkType(e)),
         };
         let data: Vec<u8> = data.bytes().collect();
 
-        Ok(Chunk::new(chunk_type, data))
+        Ok(Chunk::new(chunk_type, data).unwrap())
     }
 
     #[test]
     fn test_from_chunks() {
         let chunks = testing_chunks();
-        let png = Png::from_chunks(chunks);
+        let png = Png::from_chunks_unchecked(chunks);
 
         assert_eq!(png.chunks().len(), 3);
     }
@@ -175,6 +674,22 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_non_png_signature() {
+        let jpeg = [
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10, 0x4a, 0x46, 0x49, 0x46, 0x00,
+        ];
+        let err = Png::try_from(&jpeg[..]).err().unwrap();
+        assert_eq!(err.to_string(), "not a PNG: bad signature");
+
+        let truncated = &Png::STANDARD_HEADER[..3];
+        let err = Png::try_from(truncated).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "not a PNG: input is 3 bytes, shorter than the 8-byte signature"
+        );
+    }
+
     #[test]
     fn test_invalid_chunk() {
         let mut chunk_bytes: Vec<u8> = testing_chunks()
@@ -197,6 +712,30 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_lenient_from_bytes() {
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+        let png = Png::try_from_lenient(&bytes, false).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert!(!png.chunks()[2].verify_crc());
+    }
+
+    #[test]
+    fn test_fix_crcs() {
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        let mut png = Png::try_from_lenient(&bytes, false).unwrap();
+        assert_eq!(png.fix_crcs(), 1);
+        assert_eq!(png.fix_crcs(), 0);
+        assert_eq!(png.as_bytes(), testing_png().as_bytes());
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -204,6 +743,39 @@ mod tests {
         assert_eq!(chunks.len(), 3);
     }
 
+    #[test]
+    fn test_find() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let chunk = png
+            .find(|c| !c.chunk_type().is_critical() && c.length() > 8)
+            .unwrap();
+        assert!(!chunk.chunk_type().is_critical());
+        assert!(png.find(|c| c.length() > 1 << 20).is_none());
+    }
+
+    #[test]
+    fn test_filter() {
+        let png = testing_png();
+        let types: Vec<String> = png
+            .filter(|c| c.chunk_type().is_critical())
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "LASt"]);
+        assert!(png.filter(|c| c.length() == 0).is_empty());
+    }
+
+    #[test]
+    fn test_into_iterator() {
+        let png = testing_png();
+        let mut types = vec![];
+        for chunk in &png {
+            types.push(chunk.chunk_type().to_string());
+        }
+        assert_eq!(types, ["FrSt", "miDl", "LASt"]);
+        assert_eq!((&png).into_iter().count(), png.chunks().len());
+    }
+
     #[test]
     fn test_chunk_by_type() {
         let png = testing_png();
@@ -212,6 +784,96 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
     }
 
+    #[test]
+    fn test_from_chunks_checks_first_and_last() {
+        let chunk = |ty: &str| chunk_from_strings(ty, "").unwrap();
+
+        let png = Png::from_chunks(vec![chunk("IHDR"), chunk("ruSt"), chunk("IEND")]).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert!(matches!(
+            Png::from_chunks(vec![]),
+            Err(PngMeError::InvalidStructure(Violation::NoChunks))
+        ));
+        assert!(matches!(
+            Png::from_chunks(vec![chunk("ruSt"), chunk("IEND")]),
+            Err(PngMeError::InvalidStructure(Violation::FirstNotIhdr { .. }))
+        ));
+        assert!(matches!(
+            Png::from_chunks(vec![chunk("IHDR"), chunk("ruSt")]),
+            Err(PngMeError::InvalidStructure(Violation::LastNotIend { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_chunk_at() {
+        let png = testing_png();
+        assert_eq!(&png.chunk_at(1).unwrap().chunk_type().to_string(), "miDl");
+        assert!(png.chunk_at(png.chunks().len()).is_none());
+    }
+
+    #[test]
+    fn test_chunk_offsets() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let offsets = png.chunk_offsets();
+        assert_eq!(offsets.len(), png.chunks().len());
+        assert_eq!(offsets[..3], [8, 33, 46]);
+        for (chunk, offset) in png.chunks().iter().zip(offsets) {
+            let bytes = chunk.as_bytes();
+            assert_eq!(&PNG_FILE[offset..offset + bytes.len()], bytes.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_chunks_mut() {
+        let mut png = testing_png();
+        png.chunks_mut()[1].set_data(b"edited".to_vec()).unwrap();
+
+        let reparsed = Png::try_from(png.as_bytes().as_ref()).unwrap();
+        let chunk = reparsed.chunk_at(1).unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "miDl");
+        assert_eq!(chunk.data(), b"edited");
+        assert!(chunk.is_valid());
+    }
+
+    #[test]
+    fn test_serialized_chunk() {
+        let png = testing_png();
+        let bytes = png.serialized_chunk("miDl").unwrap();
+        assert_eq!(bytes, png.chunk_by_type("miDl").unwrap().as_bytes());
+        assert_eq!(
+            Chunk::try_from(bytes.as_slice()).unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap()
+        );
+        assert!(png.serialized_chunk("TeSt").is_none());
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "I am a second first chunk").unwrap());
+        let chunks = png.chunks_by_type("FrSt");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[0].data_as_string().unwrap(), "I am the first chunk");
+        assert_eq!(
+            &chunks[1].data_as_string().unwrap(),
+            "I am a second first chunk"
+        );
+        assert!(png.chunks_by_type("TeSt").is_empty());
+    }
+
+    #[test]
+    fn test_chunk_counts() {
+        use std::str::FromStr;
+
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "I am a second first chunk").unwrap());
+        let counts = png.chunk_counts();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&ChunkType::from_str("FrSt").unwrap()], 2);
+        assert_eq!(counts[&ChunkType::from_str("miDl").unwrap()], 1);
+        assert_eq!(counts[&ChunkType::from_str("LASt").unwrap()], 1);
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
@@ -221,6 +883,67 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_append_chunk_before_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let chunk = chunk_from_strings("TeSt", "Message").unwrap();
+        png.append_chunk(chunk.clone());
+        let chunks = png.chunks();
+        assert_eq!(&chunks[chunks.len() - 1].chunk_type().to_string(), "IEND");
+        assert_eq!(chunks[chunks.len() - 2], chunk);
+    }
+
+    #[test]
+    fn test_append_chunks() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let before = png.chunks().len();
+        let chunks: Vec<Chunk> = ["one", "two", "three"]
+            .iter()
+            .map(|message| chunk_from_strings("TeSt", message).unwrap())
+            .collect();
+        png.append_chunks(chunks.clone());
+
+        let all = png.chunks();
+        assert_eq!(all.len(), before + 3);
+        assert_eq!(&all[all.len() - 4..all.len() - 1], chunks.as_slice());
+        assert_eq!(&all[all.len() - 1].chunk_type().to_string(), "IEND");
+    }
+
+    #[test]
+    fn test_insert_chunk_before() {
+        let mut png = testing_png();
+        png.insert_chunk_before("miDl", chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "TeSt", "miDl", "LASt"]);
+    }
+
+    #[test]
+    fn test_insert_chunk_after() {
+        let mut png = testing_png();
+        png.insert_chunk_after("miDl", chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "miDl", "TeSt", "LASt"]);
+    }
+
+    #[test]
+    fn test_insert_chunk_missing_anchor() {
+        let mut png = testing_png();
+        let chunk = chunk_from_strings("TeSt", "Message").unwrap();
+        assert!(png.insert_chunk_before("IDAT", chunk.clone()).is_err());
+        assert!(png.insert_chunk_after("IDAT", chunk).is_err());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -230,6 +953,84 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_chunk_at() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "first").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "second").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "third").unwrap());
+        let removed = png.remove_chunk_at("TeSt", 1).unwrap();
+        assert_eq!(&removed.data_as_string().unwrap(), "second");
+        let remaining: Vec<String> = png
+            .chunks_by_type("TeSt")
+            .iter()
+            .map(|c| c.data_as_string().unwrap())
+            .collect();
+        assert_eq!(remaining, ["first", "third"]);
+        assert!(png.remove_chunk_at("TeSt", 2).is_err());
+    }
+
+    #[test]
+    fn test_remove_all_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "one").unwrap());
+        png.append_chunk(chunk_from_strings("miDl", "two").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "three").unwrap());
+
+        assert_eq!(png.count_chunks("TeSt"), 2);
+        assert_eq!(png.remove_all_chunks("TeSt"), 2);
+        assert_eq!(png.count_chunks("TeSt"), 0);
+        assert_eq!(png.remove_all_chunks("TeSt"), 0);
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "miDl", "LASt", "miDl"]);
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let new = chunk_from_strings("miDl", "I replaced the middle").unwrap();
+        let old = png.replace_chunk("miDl", new).unwrap();
+        assert_eq!(&old.data_as_string().unwrap(), "I am another chunk");
+        let chunk = &png.chunks()[1];
+        assert_eq!(&chunk.chunk_type().to_string(), "miDl");
+        assert_eq!(&chunk.data_as_string().unwrap(), "I replaced the middle");
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_replace_missing_chunk() {
+        let mut png = testing_png();
+        let new = chunk_from_strings("TeSt", "Message").unwrap();
+        assert!(matches!(
+            png.replace_chunk("TeSt", new),
+            Err(PngMeError::ChunkNotFound(ty)) if ty == "TeSt"
+        ));
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_png_from_reader() {
+        let mut reader = std::io::BufReader::new(&PNG_FILE[..]);
+        let png = Png::from_reader(&mut reader).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_png_read_chunks() {
+        let types: Vec<String> = Png::read_chunks(&PNG_FILE[..])
+            .unwrap()
+            .map(|chunk| chunk.unwrap().chunk_type().to_string())
+            .collect();
+        assert_eq!(types.first().unwrap(), "IHDR");
+        assert_eq!(types.last().unwrap(), "IEND");
+
+        assert!(Png::read_chunks(&PNG_FILE[1..]).is_err());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -240,7 +1041,7 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
@@ -262,6 +1063,227 @@ mod tests {
         let _png_string = format!("{}", png);
     }
 
+    #[test]
+    fn test_error_kinds() {
+        assert!(matches!(
+            Png::try_from(&PNG_FILE[1..]),
+            Err(PngMeError::BadSignature)
+        ));
+        assert!(matches!(
+            Png::try_from([].as_ref()),
+            Err(PngMeError::TooShort(0))
+        ));
+        assert!(matches!(
+            Png::try_from(&PNG_FILE[..7]),
+            Err(PngMeError::TooShort(7))
+        ));
+        let mut png = testing_png();
+        assert!(matches!(
+            png.remove_chunk("TeSt"),
+            Err(PngMeError::ChunkNotFound(chunk_type)) if chunk_type == "TeSt"
+        ));
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[32] ^= 0xff;
+        assert!(matches!(
+            Png::try_from(bytes.as_slice()),
+            Err(PngMeError::CrcMismatch { expected, actual }) if expected ^ actual == 0xff
+        ));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_open_mmap() {
+        let path = std::env::temp_dir().join(format!("pngme-{}-mmap.png", std::process::id()));
+        std::fs::write(&path, PNG_FILE).unwrap();
+        // SAFETY: nothing else touches the temporary file
+        let png = unsafe { Png::open_mmap(&path) }.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_ihdr() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.ihdr().unwrap();
+        let data = png.chunk_by_type("IHDR").unwrap().data();
+        assert_eq!(ihdr.width.to_be_bytes(), data[..4]);
+        assert_eq!(ihdr.height.to_be_bytes(), data[4..8]);
+        assert!(matches!(
+            testing_png().ihdr(),
+            Err(PngMeError::ChunkNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_total_size() {
+        let png = testing_png();
+        assert_eq!(png.total_size(), png.as_bytes().len());
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.total_size(), PNG_FILE.len());
+        let last = png.chunks().last().unwrap();
+        assert_eq!(
+            png.total_size(),
+            png.chunk_offsets().last().unwrap() + Chunk::MIN_SIZE + last.data_len()
+        );
+    }
+
+    #[test]
+    fn test_png_display() {
+        let png = testing_png();
+        let lines: Vec<String> = png.to_string().lines().map(String::from).collect();
+        assert_eq!(lines[0], "PNG signature: valid, 3 chunk(s)");
+        let chunk = &png.chunks()[1];
+        assert_eq!(
+            lines[2],
+            format!("   1  miDl  {:>10}  {:08x}", chunk.length(), chunk.crc())
+        );
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn test_as_bytes_allocates_once() {
+        let idat = Chunk::new(
+            ChunkType::from_str("IDAT").unwrap(),
+            vec![7; 4 * 1024 * 1024],
+        )
+        .unwrap();
+        let mut chunks = vec![idat; 4];
+        chunks.push(chunk_from_strings("IEND", "").unwrap());
+        let png = Png::from_chunks_unchecked(chunks);
+
+        let bytes = png.as_bytes();
+        // growing the vector would leave spare capacity behind
+        assert_eq!(bytes.capacity(), bytes.len());
+        assert_eq!(bytes.len(), png.total_size());
+        assert_eq!(Png::try_from(bytes.as_slice()).unwrap().as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"junk");
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(png.trailing_bytes(), b"junk");
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+        assert!(Png::try_from_strict(bytes.as_slice()).is_err());
+        assert!(Png::try_from_strict(&PNG_FILE[..]).is_ok());
+    }
+
+    #[test]
+    fn test_chunks_after_iend() {
+        let chunk = chunk_from_strings("RuSt", "hidden").unwrap();
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend(chunk.as_bytes());
+        let png = Png::try_from_strict(bytes.as_slice()).unwrap();
+        assert_eq!(png.chunks().last(), Some(&chunk));
+    }
+
+    #[test]
+    fn test_validate() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.validate().is_empty());
+        assert_eq!(
+            Png::from_chunks_unchecked(vec![]).validate(),
+            [Violation::NoChunks]
+        );
+    }
+
+    #[test]
+    fn test_validate_violations() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut chunks = png.chunks().to_vec();
+        let idat = chunks
+            .iter()
+            .position(|c| c.chunk_type().to_string() == "IDAT")
+            .unwrap();
+        chunks.insert(idat + 1, chunk_from_strings("TeSt", "Message").unwrap());
+        chunks.insert(idat + 2, chunks[idat].clone());
+        chunks.push(chunks[0].clone());
+        let last = chunks.len() - 1;
+        let ihdr = ChunkType::from_str("IHDR").unwrap();
+
+        assert_eq!(
+            Png::from_chunks_unchecked(chunks).validate(),
+            [
+                Violation::LastNotIend {
+                    index: last,
+                    found: ihdr
+                },
+                Violation::IdatNotContiguous { index: idat + 2 },
+                Violation::Duplicate {
+                    index: last,
+                    chunk_type: ihdr
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_invalid_crc() {
+        let mut bytes = PNG_FILE.to_vec();
+        // last byte of the IHDR CRC
+        bytes[32] ^= 0xff;
+        let png = Png::try_from_lenient(&bytes, false).unwrap();
+        assert_eq!(
+            png.validate(),
+            [Violation::InvalidCrc {
+                index: 0,
+                chunk_type: ChunkType::from_str("IHDR").unwrap()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_verify_crcs() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        // a small buffer makes the IDAT data arrive in several pieces
+        let mut reader = std::io::BufReader::with_capacity(16, &PNG_FILE[..]);
+        assert_eq!(
+            Png::verify_crcs(&mut reader).unwrap(),
+            CrcReport {
+                chunks: png.chunks().len(),
+                mismatch: None,
+                trailing: 0
+            }
+        );
+
+        let mut bytes = PNG_FILE.to_vec();
+        // last byte of the sRGB CRC, the second chunk
+        bytes[45] ^= 0xff;
+        let report = Png::verify_crcs(&mut &bytes[..]).unwrap();
+        assert_eq!(report.chunks, 1);
+        let mismatch = report.mismatch.unwrap();
+        assert_eq!(mismatch.index, 1);
+        assert_eq!(mismatch.offset, 33);
+        assert_eq!(&mismatch.chunk_type.to_string(), "sRGB");
+        assert_eq!(mismatch.actual, mismatch.expected ^ 0xff);
+
+        assert!(Png::verify_crcs(&mut &PNG_FILE[..100]).is_err());
+    }
+
+    #[test]
+    fn test_verify_crcs_after_iend() {
+        let chunks = Png::try_from(&PNG_FILE[..]).unwrap().chunks().len();
+        let appended = chunk_from_strings("ruSt", "after the end").unwrap();
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend(appended.as_bytes());
+        bytes.extend(b"garbage");
+
+        let report = Png::verify_crcs(&mut bytes.as_slice()).unwrap();
+        assert_eq!(report.chunks, chunks + 1);
+        assert_eq!(report.mismatch, None);
+        assert_eq!(report.trailing, 7);
+
+        let last = bytes.len() - 8;
+        bytes[last] ^= 0xff;
+        let mismatch = Png::verify_crcs(&mut bytes.as_slice())
+            .unwrap()
+            .mismatch
+            .unwrap();
+        assert_eq!(mismatch.index, chunks);
+        assert_eq!(mismatch.offset, PNG_FILE.len() as u64);
+    }
+
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
     const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
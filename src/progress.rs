@@ -0,0 +1,115 @@
+use std::io::{self, Read, Write};
+
+/// Files smaller than this are read and written too quickly to be worth
+/// reporting progress for.
+pub const THRESHOLD: u64 = 32 * 1024 * 1024;
+
+/// Shows how much of `total` bytes has been processed as a percentage,
+/// rewriting a single line of `out`.
+pub struct Progress<W: Write> {
+    label: &'static str,
+    total: u64,
+    done: u64,
+    percent: Option<u64>,
+    out: W,
+}
+
+impl<W: Write> Progress<W> {
+    pub fn new(label: &'static str, total: u64, out: W) -> Self {
+        Self {
+            label,
+            total,
+            done: 0,
+            percent: None,
+            out,
+        }
+    }
+    pub fn advance(&mut self, n: usize) {
+        self.done += n as u64;
+        let percent = match self.total {
+            0 => 100,
+            total => (self.done * 100 / total).min(100),
+        };
+        if self.percent == Some(percent) {
+            return;
+        }
+        self.percent = Some(percent);
+        // failing to show progress is no reason to fail the operation
+        let _ = write!(self.out, "\r{}: {percent:>3}%", self.label);
+        if percent == 100 {
+            let _ = writeln!(self.out);
+        }
+        let _ = self.out.flush();
+    }
+}
+
+/// Reader that reports the bytes read through it to a `Progress`.
+pub struct ProgressReader<R, W: Write> {
+    inner: R,
+    progress: Progress<W>,
+}
+
+impl<R, W: Write> ProgressReader<R, W> {
+    pub fn new(inner: R, progress: Progress<W>) -> Self {
+        Self { inner, progress }
+    }
+}
+
+impl<R: Read, W: Write> Read for ProgressReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.advance(n);
+        Ok(n)
+    }
+}
+
+/// Writes `bytes` to `out` a piece at a time, reporting each to `progress`.
+pub fn write_all<W: Write, P: Write>(
+    out: &mut W,
+    bytes: &[u8],
+    progress: &mut Progress<P>,
+) -> io::Result<()> {
+    for piece in bytes.chunks(1024 * 1024) {
+        out.write_all(piece)?;
+        progress.advance(piece.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_reader() {
+        let data = vec![7; 1000];
+        let mut shown = vec![];
+        let mut read = vec![];
+        let mut reader =
+            ProgressReader::new(data.as_slice(), Progress::new("reading", 1000, &mut shown));
+        reader.read_to_end(&mut read).unwrap();
+
+        assert_eq!(read, data);
+        let shown = String::from_utf8(shown).unwrap();
+        assert!(shown.ends_with("\rreading: 100%\n"));
+    }
+
+    #[test]
+    fn test_write_all() {
+        let bytes = vec![1; 3 * 1024 * 1024];
+        let mut out = vec![];
+        let mut shown = vec![];
+        write_all(
+            &mut out,
+            &bytes,
+            &mut Progress::new("writing", bytes.len() as u64, &mut shown),
+        )
+        .unwrap();
+
+        assert_eq!(out, bytes);
+        assert_eq!(
+            String::from_utf8(shown).unwrap(),
+            "\rwriting:  33%\rwriting:  66%\rwriting: 100%\n"
+        );
+    }
+}
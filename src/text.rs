@@ -0,0 +1,222 @@
+use std::io;
+use thiserror::Error;
+
+use crate::chunk::{Chunk, ChunkError};
+use crate::chunk_type::ChunkType;
+use crate::compression;
+
+/// Keyword and text of a `tEXt`, `zTXt` or `iTXt` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    pub keyword: String,
+    pub text: String,
+}
+
+#[derive(Debug, Error)]
+pub enum TextError {
+    #[error("{0} is not a text chunk type")]
+    NotText(String),
+    #[error("missing null separator")]
+    MissingSeparator,
+    #[error("unknown compression method {0}")]
+    UnknownCompression(u8),
+    #[error("text is not valid UTF-8")]
+    InvalidUtf8,
+    #[error("invalid keyword '{0}': must be 1-79 printable Latin-1 characters")]
+    InvalidKeyword(String),
+    #[error("text contains characters outside Latin-1")]
+    NotLatin1,
+    #[error("can't inflate compressed text: {0}")]
+    Inflate(#[from] io::Error),
+}
+
+impl TextChunk {
+    /// Chunk types that hold text.
+    pub const TYPES: [&'static str; 3] = ["tEXt", "zTXt", "iTXt"];
+    /// Most bytes compressed text may expand to; hostile files can't make
+    /// reading their text use up memory.
+    pub const MAX_INFLATED_LEN: usize = 8 * 1024 * 1024;
+
+    /// Checks that `keyword` and `text` can be stored in a `tEXt` chunk.
+    pub fn new(keyword: &str, text: &str) -> Result<Self, TextError> {
+        let printable = |c: char| matches!(c, ' '..='~' | '\u{a1}'..='\u{ff}');
+        let len = keyword.chars().count();
+        if !(1..=79).contains(&len) || !keyword.chars().all(printable) {
+            return Err(TextError::InvalidKeyword(keyword.to_string()));
+        }
+        if text.chars().any(|c| c > '\u{ff}') {
+            return Err(TextError::NotLatin1);
+        }
+        Ok(Self {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    /// Builds a `tEXt` chunk holding `keyword`, a null byte and `text`.
+    pub fn to_chunk(&self) -> Result<Chunk, ChunkError> {
+        // both are Latin-1, so every char fits in a byte
+        let data = self
+            .keyword
+            .chars()
+            .chain(std::iter::once('\0'))
+            .chain(self.text.chars())
+            .map(|c| c as u8)
+            .collect();
+        Chunk::new(ChunkType::try_from(*b"tEXt")?, data)
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = TextError;
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        let chunk_type = chunk.type_str();
+        let (keyword, rest) = split_null(chunk.data())?;
+        let keyword = latin1(keyword);
+        let text = match chunk_type.as_str() {
+            "tEXt" => latin1(rest),
+            "zTXt" => {
+                let (&method, compressed) =
+                    rest.split_first().ok_or(TextError::MissingSeparator)?;
+                latin1(&inflate(method, compressed)?)
+            }
+            "iTXt" => {
+                let [flag, method, rest @ ..] = rest else {
+                    return Err(TextError::MissingSeparator);
+                };
+                // skip the language tag and translated keyword
+                let (_, rest) = split_null(rest)?;
+                let (_, text) = split_null(rest)?;
+                let text = if *flag == 0 {
+                    text.to_vec()
+                } else {
+                    inflate(*method, text)?
+                };
+                String::from_utf8(text).map_err(|_| TextError::InvalidUtf8)?
+            }
+            _ => return Err(TextError::NotText(chunk_type)),
+        };
+        Ok(Self { keyword, text })
+    }
+}
+
+fn split_null(data: &[u8]) -> Result<(&[u8], &[u8]), TextError> {
+    let i = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(TextError::MissingSeparator)?;
+    Ok((&data[..i], &data[i + 1..]))
+}
+
+/// Text chunks other than `iTXt` are ISO-8859-1, whose code points map
+/// directly to chars.
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+fn inflate(method: u8, data: &[u8]) -> Result<Vec<u8>, TextError> {
+    // zlib deflate is the only method the specification defines
+    if method != 0 {
+        return Err(TextError::UnknownCompression(method));
+    }
+    Ok(compression::inflate_limited(
+        data,
+        TextChunk::MAX_INFLATED_LEN,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data).unwrap()
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_text() {
+        let text = TextChunk::try_from(&chunk("tEXt", b"Title\0Caf\xe9".to_vec())).unwrap();
+        assert_eq!(text.keyword, "Title");
+        assert_eq!(text.text, "Café");
+    }
+
+    #[test]
+    fn test_compressed_text() {
+        let mut data = b"Comment\0\0".to_vec();
+        data.extend(deflate(b"squeezed"));
+        let text = TextChunk::try_from(&chunk("zTXt", data)).unwrap();
+        assert_eq!(text.keyword, "Comment");
+        assert_eq!(text.text, "squeezed");
+    }
+
+    #[test]
+    fn test_compressed_text_limit() {
+        let mut data = b"Comment\0\0".to_vec();
+        data.extend(deflate(&vec![b'a'; TextChunk::MAX_INFLATED_LEN + 1]));
+        assert!(matches!(
+            TextChunk::try_from(&chunk("zTXt", data)),
+            Err(TextError::Inflate(_))
+        ));
+    }
+
+    #[test]
+    fn test_international_text() {
+        let data = "Title\0\0\0ja\0タイトル\0猫".as_bytes().to_vec();
+        let text = TextChunk::try_from(&chunk("iTXt", data)).unwrap();
+        assert_eq!(text.keyword, "Title");
+        assert_eq!(text.text, "猫");
+
+        let mut data = b"Title\0\x01\0\0\0".to_vec();
+        data.extend(deflate("猫".as_bytes()));
+        let text = TextChunk::try_from(&chunk("iTXt", data)).unwrap();
+        assert_eq!(text.text, "猫");
+    }
+
+    #[test]
+    fn test_to_chunk() {
+        let chunk = TextChunk::new("Title", "Café").unwrap().to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(chunk.data(), b"Title\0Caf\xe9");
+        let text = TextChunk::try_from(&chunk).unwrap();
+        assert_eq!(text, TextChunk::new("Title", "Café").unwrap());
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_keywords() {
+        assert!(TextChunk::new("", "x").is_err());
+        assert!(TextChunk::new(&"k".repeat(80), "x").is_err());
+        assert!(TextChunk::new("Key\0word", "x").is_err());
+        assert!(TextChunk::new("猫", "x").is_err());
+        assert!(TextChunk::new(&"k".repeat(79), "x").is_ok());
+        assert!(matches!(
+            TextChunk::new("Title", "猫"),
+            Err(TextError::NotLatin1)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_text() {
+        assert!(matches!(
+            TextChunk::try_from(&chunk("tEXt", b"no separator".to_vec())),
+            Err(TextError::MissingSeparator)
+        ));
+        assert!(matches!(
+            TextChunk::try_from(&chunk("zTXt", b"Comment\0\x01data".to_vec())),
+            Err(TextError::UnknownCompression(1))
+        ));
+        assert!(matches!(
+            TextChunk::try_from(&chunk("ruSt", b"a\0b".to_vec())),
+            Err(TextError::NotText(_))
+        ));
+    }
+}